@@ -2,6 +2,6 @@ fn main() {
     if cfg!(target_os = "windows") {
         let mut res = winres::WindowsResource::new();
         res.set_resource_file("resources.rc");
-        res.compile().unwrap();
+        res.compile().expect("Failed to compile windows resources");
     }
-}
\ No newline at end of file
+}
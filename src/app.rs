@@ -1,225 +1,226 @@
-use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::path::Path;
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
-use tray_item::{IconSource, TrayItem};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
+use sysinfo::System;
 // cfg to enable cpu render if ram gets pushy later
 
-use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
-use windows::Win32::Graphics::Dwm::{DWMWA_CLOAKED, DwmGetWindowAttribute};
-use windows::Win32::UI::Input::KeyboardAndMouse::{
-    MOD_ALT, MOD_CONTROL, RegisterHotKey, UnregisterHotKey,
+use crate::backend::{self, WindowBackend, WindowEvent, WindowId};
+use crate::classifier::{Classifier, Filtered};
+use crate::close::{CloseStrategy, Outcome, SweepReport, Target, start_sweep};
+use crate::config::{Config, ConfigError, ConfigWatcher};
+use crate::countdown::{Choice, Countdown};
+use crate::dry_run::{Candidate, Decision, candidates, decide, is_self, sweep_targets};
+use crate::hotkey::{
+    Action, Hotkey, HotkeyEvent, HotkeyListener, RegisterError, start_hotkey_listener,
 };
-
-use windows::Win32::UI::WindowsAndMessaging::{
-    EnumWindows, GA_ROOTOWNER, GWL_EXSTYLE, GetAncestor, GetLastActivePopup,
-    GetMessageW, GetWindowLongW, GetWindowThreadProcessId, IsWindowVisible,
-    MSG, PostMessageW, WM_CLOSE, WM_HOTKEY, WS_EX_TOOLWINDOW,
-};
-use windows::core::{BOOL, Result};
-
-enum Message {
-    Quit,
-    Hide,
-    Unhide,
+use crate::policy::Layers;
+use crate::processes::{ProcessInfo, ProcessList, ToClose, collect_processes};
+use crate::profiles::{Profile, Profiles};
+use crate::rules::Rule;
+use crate::ruleset::{Merge, RuleSet};
+use crate::schedule::{Hours, When};
+use crate::scheduler::{ScheduledSweep, Scheduler, next_sweep};
+use crate::session::{LAST_SESSION, SavedApp, Sessions};
+use crate::tray::{self, Tray, TrayEvent};
+use crate::watch::Watch;
+use crate::worker::{SweepRules, Swept, Update, Worker, enumerate};
+
+// how many opened and closed windows the activity list keeps
+const ACTIVITY: usize = 20;
+
+// how long changes in the window have to be left alone before they're written
+const SETTLE: Duration = Duration::from_millis(500);
+
+pub fn strip_file_extension(s: &str) -> String {
+    Path::new(s)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| s.to_owned())
 }
 
+// a "+" row per executable, with how many of it are open, unfolding into its windows.
+// returns the windows the user closed one by one
+fn process_rows(
+    ui: &mut egui::Ui,
+    list: &ProcessList,
+    add_to: &mut BTreeSet<Rule>,
+) -> Vec<WindowId> {
+    let mut to_close = Vec::new();
+
+    ui.horizontal(|ui| {
+        ui.add_sized([50.0, 20.0], egui::Label::new("Count"));
+        ui.add_sized([50.0, 20.0], egui::Label::new("Process Name"));
+    });
 
-#[allow(unsafe_code)]
-pub fn is_pseudo_open_in_taskbar(mut hwnd: HWND, show_all_processes: bool) -> bool {
-    if show_all_processes {
-        return true;
+    for (name, processes) in list {
+        let id = ui.make_persistent_id(name);
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                if ui.button("+").clicked() {
+                    add_to.insert(Rule::name(name));
+                }
+                let pids = processes
+                    .keys()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                ui.add_sized([50.0, 20.0], egui::Label::new(processes.len().to_string()))
+                    .on_hover_text(format!("PID: {pids}"));
+                ui.add_sized([0.0, 20.0], egui::Label::new(strip_file_extension(name)));
+            })
+            .body(|ui| {
+                for window in processes.values().flat_map(|process| &process.windows) {
+                    if !window.in_taskbar {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .small_button("x")
+                            .on_hover_text("Close just this window")
+                            .clicked()
+                        {
+                            to_close.push(window.id);
+                        }
+                        ui.label(&window.title);
+                    });
+                }
+            });
     }
-    unsafe {
-        // Finding a visible popup
-        let root = GetAncestor(hwnd, GA_ROOTOWNER);
-        let mut last = root;
-        loop {
-            let popup = GetLastActivePopup(last);
-            if popup == last {
-                break;
-            }
-            if IsWindowVisible(popup).as_bool() {
-                last = popup;
-                break;
-            }
-            last = popup;
-        }
 
-        // if the root is invisible but the popup is visible, we use the popup
-        if !IsWindowVisible(last).as_bool() {
-            let popup = GetLastActivePopup(root);
-            if IsWindowVisible(popup).as_bool() {
-                last = popup;
-            }
-        }
-
-        hwnd = last;
-
-        // Is cloaked?
-        let mut cloaked: u32 = 0;
-        if DwmGetWindowAttribute(
-            hwnd,
-            DWMWA_CLOAKED,
-            &mut cloaked as *mut _ as _,
-            size_of::<u32>() as u32,
-        )
-        .is_ok()
-        {
-            if cloaked != 0 {
-                return false;
-            }
-        }
-
-        let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
-
-        // Is tool window
-        if ex_style & WS_EX_TOOLWINDOW.0 != 0 {
-            return false;
-        }
-
-        // Is visible?
-        if IsWindowVisible(hwnd).as_bool() == false {
-            return false;
-        }
+    to_close
+}
 
-        true
+// plain names like the rest of the ui, patterns as typed
+fn rule_label(rule: &Rule) -> String {
+    if rule.is_plain() {
+        strip_file_extension(rule.as_str())
+    } else {
+        rule.to_string()
     }
 }
 
-// #[allow(unsafe_code)]
-// pub fn is_top_level(hwnd: HWND) -> bool {
-//     unsafe {
-//         let style = GetWindowLongW(hwnd, GWL_STYLE) as u32;
-//
-//         let is_not_child = (style & WS_CHILD.0) == 0;
-//
-//         is_not_child
-//     }
-// }
-
-// closing an app by its process id
-#[allow(unsafe_code)]
-pub fn close_by_pid(target_pid: &u32) -> Result<()> {
-    extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
-        unsafe {
-            let mut pid = 0;
-            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+fn rule_rows(
+    ui: &mut egui::Ui,
+    rules: &mut BTreeSet<Rule>,
+    comments: &BTreeMap<Rule, String>,
+    layers: &Layers,
+) {
+    let mut to_remove = None;
+
+    for rule in &*rules {
+        let lock = layers.lock(rule);
+        ui.horizontal(|ui| {
+            let remove = ui.add_enabled(lock.is_none(), egui::Button::new("-"));
+            if remove.clicked() {
+                to_remove = Some(rule.clone());
+            }
 
-            let target_pid = lparam.0 as usize as u32;
-            if pid == target_pid {
-                let _ = PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0));
+            let label = ui.add_sized([50.0, 20.0], egui::Label::new(rule_label(rule)));
+            if let Some(comment) = comments.get(rule) {
+                label.on_hover_text(comment);
             }
-        }
-        BOOL(1) // continuing enumeration
+            if let Some(lock) = lock {
+                ui.weak("locked")
+                    .on_hover_text(format!("Set by {}", lock.source.label()));
+            }
+        });
     }
 
-    unsafe {
-        EnumWindows(Some(enum_windows_proc), LPARAM(*target_pid as isize));
+    if let Some(rule) = to_remove {
+        rules.remove(&rule);
     }
-    Ok(())
 }
 
-#[allow(unsafe_code)]
-pub fn get_hwnd_by_pid(target_pid: u32) -> Option<HWND> {
-    extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
-        unsafe {
-            let mut pid = 0;
-            GetWindowThreadProcessId(hwnd, Some(&mut pid));
-
-            let data = lparam.0 as *mut (u32, Option<HWND>);
-            let (target_pid, found_hwnd) = unsafe { &mut *data };
-            if pid == *target_pid {
-                *found_hwnd = Some(hwnd);
-                return BOOL(0);
-            };
-        }
-        BOOL(1)
-    }
-
-    let mut data = (target_pid, None);
-    unsafe {
-        EnumWindows(
-            Some(enum_windows_proc),
-            LPARAM(&mut data as *mut _ as isize),
+// for the rules the "+" buttons can't make
+fn rule_input(ui: &mut egui::Ui, rules: &mut BTreeSet<Rule>, text: &mut String) {
+    let parsed = text.parse::<Rule>();
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(text).on_hover_text(
+            "A process name, or field=glob / field~=regex on name, path, title, class or args.\n\
+             e.g. path=/opt/jetbrains/** or title~=\".*- Visual Studio Code$\"",
         );
+        if ui
+            .add_enabled(parsed.is_ok(), egui::Button::new("+"))
+            .clicked()
+        {
+            if let Ok(rule) = &parsed {
+                rules.insert(rule.clone());
+                text.clear();
+            }
+        }
+    });
+    if let (Err(e), false) = (&parsed, text.trim().is_empty()) {
+        ui.colored_label(ui.visuals().error_fg_color, e.to_string());
     }
-
-    data.1
 }
 
-pub fn strip_file_extension(s: &String) -> String {
-    Path::new(s)
-        .file_stem()
-        .unwrap()
-        .to_string_lossy()
-        .into_owned()
+// from before the config file. eframe keeps its storage as a ron map of ron strings, the
+// app is under `APP_KEY`
+fn saved_state() -> Option<Expurgate> {
+    let path = eframe::storage_dir("expurgate")?.join("app.ron");
+    let text = std::fs::read_to_string(&path).ok()?;
+    let storage: HashMap<String, String> = ron::from_str(&text)
+        .inspect_err(|e| log::warn!("Failed to read {}: {e}", path.display()))
+        .ok()?;
+    ron::from_str(storage.get(eframe::APP_KEY)?)
+        .inspect_err(|e| log::warn!("Failed to read the saved state: {e}"))
+        .ok()
 }
 
-#[allow(unsafe_code)]
-pub fn register_kill_hotkey() {
-    unsafe {
-        RegisterHotKey(None, 1, MOD_CONTROL | MOD_ALT, 'J' as u32)
-            .expect("Failed to register hotkey");
-    }
+/// Something a hotkey can be bound to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Binding {
+    /// Sweeping with this profile.
+    Profile(String),
+    Action(Action),
 }
 
-// future use
-#[allow(unsafe_code)]
-pub fn unregister_hotkey(id: i32) {
-    unsafe {
-        UnregisterHotKey(Option::from(HWND(std::ptr::null_mut())), id)
-            .expect("Failed to unregister hotkey");
+impl Binding {
+    fn label(&self) -> String {
+        match self {
+            Self::Profile(name) => format!("the {name} profile"),
+            Self::Action(action) => action.label().to_owned(),
+        }
     }
 }
 
-// handling closing with a hotkey
-#[allow(unsafe_code)]
-pub fn start_kill_hotkey_listener(tx: Sender<HotkeyEvent>) {
-    thread::spawn(move || unsafe {
-        let mut msg = MSG::default();
-        register_kill_hotkey();
-
-        while GetMessageW(&mut msg, None, 0, 0).into() {
-            if msg.message == WM_HOTKEY && msg.wParam.0 == 1 {
-                tx.send(HotkeyEvent::Kill).ok();
-            }
+// egui only knows a key by its name, which happens to be what `Hotkey` parses
+fn captured_hotkey(modifiers: egui::Modifiers, key: egui::Key) -> Result<Hotkey, String> {
+    let mut text = String::new();
+    // `command` is ctrl everywhere but on macs, where it's the same as `mac_cmd`
+    let logo = modifiers.mac_cmd || (modifiers.command && !modifiers.ctrl);
+    for (held, name) in [
+        (modifiers.ctrl, "Ctrl+"),
+        (modifiers.alt, "Alt+"),
+        (modifiers.shift, "Shift+"),
+        (logo, "Super+"),
+    ] {
+        if held {
+            text.push_str(name);
         }
-    });
-}
-
-// making sure we don't try to kill some system process or helper
-// i'm going to anyway tho, i'm certain lol
-// TODO: make this togglable later
-pub fn loosely_check_if_real_app(pid: &u32, name: &String) -> bool {
-    // system processes
-    let lower = name.to_ascii_lowercase();
-    if lower.contains("service")
-        || lower.contains("helper")
-        || lower.contains("overlay")
-        || lower.contains("tray")
-        || lower.contains("host")
-        || lower.contains("broker")
-        || lower.contains("container")
-        || lower.contains("runtime")
-        || lower.contains("svchost")
-        || lower.contains("dwm")
-        // TODO: handle these two manually later
-        || lower.contains("explorer")
-        || lower.contains("taskmgr")
-        || *pid == 0
-        || *pid == 4
-    {
-        return false;
-    }
-    true
+    }
+    text.push_str(key.name());
+    text.parse()
 }
 
-#[derive(Debug)]
-pub enum HotkeyEvent {
-    Kill,
+fn strategy_ui(ui: &mut egui::Ui, strategy: &mut CloseStrategy) {
+    ui.horizontal(|ui| {
+        ui.label("Wait");
+        ui.add(egui::DragValue::new(&mut strategy.grace_secs).suffix(" s"));
+        ui.label("after asking politely");
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut strategy.terminate, "then terminate, wait");
+        ui.add_enabled(
+            strategy.terminate,
+            egui::DragValue::new(&mut strategy.terminate_grace_secs).suffix(" s"),
+        );
+    });
+    ui.checkbox(&mut strategy.force_kill, "then force kill")
+        .on_hover_text("Unsaved changes are lost, the app gets no say in it.");
 }
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
@@ -230,388 +231,2611 @@ pub struct Expurgate {
     sys: System,
 
     #[serde(skip)]
-    processlist: BTreeMap<String, u32>,
+    processlist: ProcessList,
+
+    #[serde(skip)]
+    unf_processlist: ProcessList,
+
+    /// Decides what's a real app, see [`Classifier`].
+    classifier: Classifier,
+
+    #[serde(skip)]
+    new_classifier_rule: String,
+
+    // what got filtered out of processlist and why, by name
+    #[serde(skip)]
+    filtered: BTreeMap<String, Filtered>,
 
+    // taskbar windows opening and closing, newest first
     #[serde(skip)]
-    unf_processlist: BTreeMap<String, u32>,
+    activity: VecDeque<(jiff::Zoned, WindowEvent, String)>,
 
+    // what processlist was filtered with, there's nothing to redo while it's the same
     #[serde(skip)]
-    filter_to_remove: HashSet<String>,
+    filtered_with: Option<(Profile, Classifier)>,
 
     #[serde(skip)]
     selected_process_pid: Option<u32>,
 
-    allowlist: BTreeSet<String>,
+    profiles: Profiles,
 
     #[serde(skip)]
-    kill_hotkey_registered: bool,
+    new_profile_name: String,
 
+    /// Hotkeys for everything that isn't sweeping with a given profile.
+    hotkeys: BTreeMap<Action, Hotkey>,
+
+    #[serde(skip)]
+    hotkey_listener: Option<HotkeyListener>,
+
+    // waiting for a key combination to bind to this
+    #[serde(skip)]
+    capturing: Option<Binding>,
+
+    // hotkeys that didn't register, with why and what's free instead
+    #[serde(skip)]
+    hotkey_problems: BTreeMap<Hotkey, (RegisterError, Vec<Hotkey>)>,
+
+    // rebinds the desktop hasn't answered yet, only the last answer is about what's bound
+    #[serde(skip)]
+    hotkeys_pending: usize,
+
+    #[serde(skip)]
+    hotkey_message: Option<String>,
+
+    // from before profiles, `migrate` moves them into the active one
+    #[serde(skip_serializing)]
+    allowlist: Option<BTreeSet<Rule>>,
+    #[serde(skip_serializing)]
+    killlist: Option<BTreeSet<Rule>>,
+    #[serde(skip_serializing)]
+    close_strategy: Option<CloseStrategy>,
+    #[serde(skip_serializing)]
+    strategy_overrides: Option<BTreeMap<String, CloseStrategy>>,
+
+    #[serde(skip)]
+    new_allow_rule: String,
+
+    // keeps the process lists fresh and sweeps on hotkeys
     #[serde(skip)]
-    hotkey_rx: Receiver<HotkeyEvent>,
+    worker: Worker,
 
     show_all_processes: bool,
 
-    killlist: BTreeSet<String>,
-}
+    #[serde(skip)]
+    new_kill_rule: String,
 
-impl Default for Expurgate {
-    fn default() -> Self {
-        // dummy sender
-        let (_tx, rx) = mpsc::channel();
+    /// Keeps blocked apps closed, see [`Watch`].
+    watch: Watch,
 
-        Self {
-            sys: System::new_all(),
-            processlist: BTreeMap::new(),
-            unf_processlist: BTreeMap::new(),
-            filter_to_remove: HashSet::new(),
-            selected_process_pid: None,
-            allowlist: BTreeSet::new(),
-            kill_hotkey_registered: false,
-            hotkey_rx: rx,
-            show_all_processes: false,
-            killlist: BTreeSet::new(),
-        }
-    }
-}
+    #[serde(skip)]
+    new_block_rule: String,
 
-impl Expurgate {
-    /// Called once before the first frame.
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let ctx = cc.egui_ctx.clone();
+    #[serde(skip)]
+    new_watch_hours: String,
 
-        thread::spawn({
-            let ctx = ctx.clone();
-            move || {
-                let mut tray = TrayItem::new(
-                    "Tray",
-                    IconSource::Resource("icon"),
-                )
-                    .unwrap();
+    /// Sweeps that run by themselves, see [`Scheduler`].
+    scheduled: Vec<ScheduledSweep>,
+
+    #[serde(skip)]
+    scheduler: Scheduler,
 
-                tray.add_label("Tray Label").unwrap();
+    #[serde(skip)]
+    new_schedule: String,
+
+    #[serde(skip)]
+    new_schedule_profile: String,
 
-                tray.add_menu_item("Hello", || {
-                    println!("Hello!");
-                })
-                    .unwrap();
+    #[serde(skip)]
+    new_override_name: String,
 
-                tray.inner_mut().add_separator().unwrap();
+    /// Keeps what each sweep closed as the last session, to restore later.
+    snapshot_sweeps: bool,
 
-                let (tx, rx) = mpsc::sync_channel(1);
+    #[serde(skip)]
+    sessions: Sessions,
 
-                let hide_tx = tx.clone();
-                tray.add_menu_item("Hide", move || {
-                    hide_tx.send(Message::Hide).unwrap();
-                })
-                    .unwrap();
+    // where they're kept, only in memory without one
+    #[serde(skip)]
+    sessions_path: Option<PathBuf>,
 
-                let unhide_tx = tx.clone();
-                tray.add_menu_item("Unhide", move || {
-                    unhide_tx.send(Message::Unhide).unwrap();
-                })
-                    .unwrap();
+    #[serde(skip)]
+    new_session_name: String,
 
+    // nothing's written without one
+    #[serde(skip)]
+    config_path: Option<PathBuf>,
 
-                tray.inner_mut().add_separator().unwrap();
+    // what's in the file as far as we know, to tell our changes from everyone else's
+    #[serde(skip)]
+    written: Config,
 
-                let quit_tx = tx.clone();
-                tray.add_menu_item("Quit", move || {
-                    quit_tx.send(Message::Quit).unwrap();
-                })
-                    .unwrap();
+    #[serde(skip)]
+    config_watcher: ConfigWatcher,
 
-                loop {
-                    match rx.recv() {
-                        Ok(Message::Quit) => {
-                            println!("Quit");
-                            break;
-                        }
-                        Ok(Message::Hide) => {
-                            println!("Hide");
-                            // We hackily do the hiding by just making the app 0 pixels in size
-                            // The visibility of the window never toggles back because of an eFrame bug (egui #5229)
-                            // so oh well
-                            let viewport = egui::ViewportId::ROOT;
-                            ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::Decorations(false));
-                            ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::InnerSize([0.0, 0.0].into()));
-                            ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::OuterPosition(egui::pos2(-10000.0, -10000.0)));
-                            ctx.request_repaint();
-                        },
-                        Ok(Message::Unhide) => {
-                            println!("Unhide");
-                            let viewport = egui::ViewportId::ROOT;
-                            ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::Decorations(true));
-                            ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::InnerSize([350.0, 480.0].into()));
-                            ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::OuterPosition(egui::pos2(200.0, 200.0)));
-                            ctx.request_repaint();
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        });
+    // when the config last changed in the window, it's written once that's a while ago
+    #[serde(skip)]
+    config_touched: Option<Instant>,
+
+    // it didn't read, so nothing gets written over it until it does
+    #[serde(skip)]
+    config_problem: Option<String>,
+
+    // rules from above the user's config, in every profile and never in the file
+    #[serde(skip)]
+    layers: Layers,
+
+    // where rule sets get exported to and imported from
+    #[serde(skip)]
+    rule_set_path: String,
+
+    // an import waiting on someone to go through its conflicts, and for which profile
+    #[serde(skip)]
+    reviewing: Option<(String, Merge)>,
+
+    #[serde(skip)]
+    rule_set_message: Option<String>,
+
+    /// How long after a sweep it can still be undone.
+    undo_secs: u64,
+
+    #[serde(skip)]
+    undo_until: Option<Instant>,
+
+    #[serde(skip)]
+    undo_message: Option<String>,
 
+    // asked for while the sweep was still closing things, it goes once that's done
+    #[serde(skip)]
+    undo_queued: bool,
+
+    #[serde(skip)]
+    session_message: Option<String>,
+
+    // a hotkey sweep waiting to happen
+    #[serde(skip)]
+    countdown: Option<Countdown>,
+
+    // whether the dry run goes by the button or the hotkey
+    #[serde(skip)]
+    dry_run_killlist: bool,
+
+    #[serde(skip)]
+    last_sweep: Option<SweepReport>,
+
+    #[serde(skip)]
+    sweep_rx: Option<Receiver<(Target, Outcome)>>,
+
+    #[serde(skip)]
+    tray: Tray,
+
+    #[serde(skip)]
+    backend: Arc<dyn WindowBackend>,
+}
+
+// serde makes one of these for every load, connecting to the display for each would be a waste
+impl Default for Expurgate {
+    fn default() -> Self {
+        Self::with_backend(Arc::new(backend::UnsupportedBackend))
+    }
+}
+
+impl Expurgate {
+    /// Called once before the first frame.
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        let mut app: Expurgate = if let Some(storage) = cc.storage {
+        // only for when there's no config file yet
+        let mut app: Self = if let Some(storage) = cc.storage {
             eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
         } else {
             Default::default()
         };
+        app.backend = backend::native();
+        app.migrate();
+        app.layers = Layers::load();
+        app.open_config();
+        app.open_sessions();
+        if let Some(path) = app.config_path.clone() {
+            let ctx = cc.egui_ctx.clone();
+            app.config_watcher = ConfigWatcher::spawn(path, move || ctx.request_repaint());
+        }
 
-        // handling the politely killing listener
+        // hotkeys go to the worker, it sweeps without us where it can
         let (tx, rx) = mpsc::channel::<HotkeyEvent>();
-        start_kill_hotkey_listener(tx);
-        app.hotkey_rx = rx;
+        let ctx = cc.egui_ctx.clone();
+        app.worker = Worker::spawn(app.backend.clone(), rx, app.sweep_rules(), move || {
+            ctx.request_repaint();
+        });
+        app.hotkey_listener = Some(start_hotkey_listener(
+            tx,
+            app.worker.poker(),
+            app.worker.answered(),
+        ));
+        app.apply_hotkeys();
+        app.tray = tray::spawn(
+            cc.egui_ctx.clone(),
+            app.profiles.names().map(str::to_owned).collect(),
+            app.profiles.active_name(),
+        );
+        app.scheduler = Scheduler::spawn(
+            app.scheduled.clone(),
+            app.backend.clone(),
+            app.worker.scheduled(),
+        );
+        app.update_tooltip();
 
         app
     }
-}
 
-impl eframe::App for Expurgate {
-    /// Called by the framework to save state before shutdown.
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+    /// Whatever the window saved last time, for running without one.
+    pub fn load_saved() -> Self {
+        let mut app = saved_state().unwrap_or_default();
+        app.backend = backend::native();
+        app.migrate();
+        app.layers = Layers::load();
+        app.open_config();
+        app.open_sessions();
+        app
     }
 
-    /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
-        // For inspiration and more examples, go to https://emilk.github.io/egui
-
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            // The top panel is often a good place for a menu bar:
+    /// Everything that goes in the config file.
+    pub fn config(&self) -> Config {
+        Config {
+            version: crate::config::VERSION,
+            profiles: self.profiles.clone(),
+            classifier: self.classifier.clone(),
+            hotkeys: self.hotkeys.clone(),
+            show_all_processes: self.show_all_processes,
+            snapshot_sweeps: self.snapshot_sweeps,
+            undo_secs: self.undo_secs,
+            watch: self.watch.clone(),
+            scheduled: self.scheduled.clone(),
+        }
+    }
 
-            egui::MenuBar::new().ui(ui, |ui| {
-                egui::widgets::global_theme_preference_buttons(ui);
-            });
-        });
+    /// Takes everything from `config` and gets the hotkeys, the schedule and the tray
+    /// to catch up.
+    pub fn apply_config(&mut self, mut config: Config) {
+        self.layers.enforce(&mut config.profiles);
+        // rebinding can mean asking the desktop, and a new schedule forgets who's idle
+        let rebind = self.hotkeys != config.hotkeys
+            || self.profiles.hotkey_bindings() != config.profiles.hotkey_bindings();
+        let reschedule = self.scheduled != config.scheduled;
+        self.profiles.clone_from(&config.profiles);
+        self.classifier.clone_from(&config.classifier);
+        self.hotkeys.clone_from(&config.hotkeys);
+        self.show_all_processes = config.show_all_processes;
+        self.snapshot_sweeps = config.snapshot_sweeps;
+        self.undo_secs = config.undo_secs;
+        self.watch.follow(&config.watch);
+        self.scheduled.clone_from(&config.scheduled);
+        self.written = config;
+        self.config_problem = None;
+
+        if rebind {
+            self.apply_hotkeys();
+        }
+        if reschedule {
+            self.scheduler.set(self.scheduled.clone());
+        }
+        self.tray.set_active_profile(self.profiles.active_name());
+        self.update_tooltip();
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            // The central panel the region left after adding TopPanel's and SidePanel's
-            self.sys.refresh_processes_specifics(
-                ProcessesToUpdate::All,
-                true,
-                ProcessRefreshKind::everything().without_tasks(),
-            );
+    // without cloning anything, this runs every frame
+    fn config_changed(&self) -> bool {
+        let written = &self.written;
+        written.profiles != self.profiles
+            || written.classifier != self.classifier
+            || written.hotkeys != self.hotkeys
+            || written.show_all_processes != self.show_all_processes
+            || written.snapshot_sweeps != self.snapshot_sweeps
+            || written.undo_secs != self.undo_secs
+            || written.watch != self.watch
+            || written.scheduled != self.scheduled
+    }
 
-            // populating processlist
-            self.processlist.clear();
-            for (pid, process) in self.sys.processes() {
-                let maybe_hwnd: Option<HWND> = get_hwnd_by_pid(pid.as_u32());
-                if maybe_hwnd.is_none() {
-                    continue;
+    // the file wins over whatever eframe kept, that's only read the first time
+    fn open_config(&mut self) {
+        self.layers.enforce(&mut self.profiles);
+        self.config_path = Config::default_path();
+        let Some(path) = self.config_path.clone() else {
+            return;
+        };
+        match Config::load(&path) {
+            Ok(config) => self.apply_config(config),
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                log::info!("Moving the settings to {}", path.display());
+                if let Err(e) = self.save_config() {
+                    log::warn!("Failed to save the config: {e}");
                 }
-                if is_pseudo_open_in_taskbar(maybe_hwnd.unwrap(), false) {
-                    // we don't strip file extension at the source because we will use in the actual allowlist,
-                    // so it's removed only in display
-                    self.processlist.insert(
-                        process.name().to_string_lossy().parse().unwrap(),
-                        pid.as_u32(),
-                    );
-                };
             }
-
-            for key in &self.allowlist {
-                self.processlist.remove(key.as_str());
+            Err(e) => {
+                log::warn!("Failed to read {}: {e}", path.display());
+                self.config_problem = Some(e.to_string());
             }
+        }
+    }
 
-            // and filtering it
-            for (name, pid) in &self.processlist {
-                if !loosely_check_if_real_app(&pid, &name) || name.as_str() == "expurgate.exe" {
-                        self.filter_to_remove.insert(name.clone());
-                };
-            }
+    /// Writes the config file if anything in it changed. A file that didn't read isn't
+    /// written over, that's an error too.
+    pub fn save_config(&mut self) -> io::Result<()> {
+        let Some(path) = &self.config_path else {
+            return Ok(());
+        };
+        if let Some(problem) = &self.config_problem {
+            return Err(io::Error::other(format!(
+                "not writing over {}, it didn't read: {problem}",
+                path.display()
+            )));
+        }
+        if !self.config_changed() && path.exists() {
+            return Ok(());
+        }
+        let config = self.config();
+        let mut mine = config.clone();
+        self.layers.strip(&mut mine.profiles);
+        self.config_watcher
+            .save(&mine, path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        self.written = config;
+        Ok(())
+    }
 
-            for name in &self.filter_to_remove {
-                self.processlist.remove(name.as_str());
-            }
+    // a drag changes things every frame, the file only gets them once they've settled
+    fn save_config_when_settled(&mut self, ctx: &egui::Context) {
+        if self.config_path.is_none() || self.config_problem.is_some() || !self.config_changed() {
+            self.config_touched = None;
+            return;
+        }
+        let busy = ctx.input(|input| {
+            input.pointer.any_down()
+                || input
+                    .events
+                    .iter()
+                    .any(|event| matches!(event, egui::Event::Key { .. } | egui::Event::Text(_)))
+        });
+        let now = Instant::now();
+        let touched = self.config_touched.get_or_insert(now);
+        if busy {
+            *touched = now;
+        }
+        let left = SETTLE.saturating_sub(touched.elapsed());
+        if !left.is_zero() {
+            ctx.request_repaint_after(left);
+            return;
+        }
+        self.config_touched = None;
+        if let Err(e) = self.save_config() {
+            log::warn!("Failed to save the config: {e}");
+        }
+    }
 
-            // handling kill hotkey
-            while let Ok(e) = self.hotkey_rx.try_recv() {
-                match e {
-                    HotkeyEvent::Kill => {
-                        println!("Polite murder initiated.");
-                        for (_, pid) in &self.processlist {
-                            close_by_pid(pid).unwrap();
-                        }
-                    }
-                }
+    // someone edited the file
+    fn reload_config(&mut self, reloaded: Result<Config, ConfigError>) {
+        // what's in the file never has the locked rules, what we wrote down does
+        let reloaded = reloaded.map(|mut config| {
+            self.layers.enforce(&mut config.profiles);
+            config
+        });
+        match reloaded {
+            Ok(config) if config == self.written => self.config_problem = None,
+            Ok(config) => {
+                log::info!("Config file changed, reloading");
+                self.apply_config(config);
             }
+            Err(e) => {
+                log::warn!("Failed to reload the config file: {e}");
+                self.config_problem = Some(e.to_string());
+            }
+        }
+    }
 
-            // ui:
-
-            ui.heading("allowlist");
+    fn sweep_rules(&self) -> SweepRules {
+        SweepRules {
+            profiles: self.profiles.clone(),
+            classifier: self.classifier.clone(),
+            counting_down: self.countdown.is_some(),
+            snapshot_sweeps: self.snapshot_sweeps,
+            sessions_path: self.sessions_path.clone(),
+            watch: self.watch.clone(),
+        }
+    }
 
-            ui.separator();
+    // the lists only get cloned over when they changed, this runs every frame
+    fn sync_worker(&self) {
+        self.worker.sync_rules(|rules| {
+            if rules.profiles != self.profiles {
+                rules.profiles.clone_from(&self.profiles);
+            }
+            if rules.classifier != self.classifier {
+                rules.classifier.clone_from(&self.classifier);
+            }
+            rules.counting_down = self.countdown.is_some();
+            rules.snapshot_sweeps = self.snapshot_sweeps;
+            rules.sessions_path.clone_from(&self.sessions_path);
+            if rules.watch != self.watch {
+                rules.watch.follow(&self.watch);
+            }
+        });
+    }
 
-            ui.label("allowlist in question:");
-            egui::ScrollArea::vertical()
-                .max_height(300.0)
-                .id_salt("scrollin_allowlist_30x9403mcd2")
-                .show(ui, |ui| {
-                    ui.set_width(ui.available_width());
+    fn open_sessions(&mut self) {
+        self.sessions_path = Sessions::default_path();
+        if let Some(path) = &self.sessions_path {
+            self.sessions = Sessions::load(path);
+        }
+    }
 
-                    let mut to_remove = None;
+    // state saved before profiles had its lists at the top, those become the active profile
+    fn migrate(&mut self) {
+        self.profiles.repair();
+        let profile = self.profiles.active_mut();
+        if let Some(allowlist) = self.allowlist.take() {
+            profile.allowlist.extend(allowlist);
+        }
+        if let Some(killlist) = self.killlist.take() {
+            profile.killlist.extend(killlist);
+        }
+        if let Some(strategy) = self.close_strategy.take() {
+            profile.close_strategy = strategy;
+        }
+        if let Some(overrides) = self.strategy_overrides.take() {
+            profile.strategy_overrides.extend(overrides);
+        }
+    }
 
-                    for name in &self.allowlist {
-                        ui.horizontal(|ui| {
-                            if ui.button("-").clicked() {
-                                to_remove = Some(name.clone());
-                            }
+    /// With the given backend, the default one doesn't see any windows.
+    pub fn with_backend(backend: Arc<dyn WindowBackend>) -> Self {
+        Self {
+            sys: System::new(),
+            processlist: ProcessList::new(),
+            unf_processlist: ProcessList::new(),
+            classifier: Classifier::default(),
+            new_classifier_rule: String::new(),
+            filtered: BTreeMap::new(),
+            activity: VecDeque::new(),
+            filtered_with: None,
+            selected_process_pid: None,
+            profiles: Profiles::default(),
+            new_profile_name: String::new(),
+            hotkeys: BTreeMap::new(),
+            hotkey_listener: None,
+            capturing: None,
+            hotkey_problems: BTreeMap::new(),
+            hotkeys_pending: 0,
+            hotkey_message: None,
+            allowlist: None,
+            killlist: None,
+            close_strategy: None,
+            strategy_overrides: None,
+            new_allow_rule: String::new(),
+            worker: Worker::default(),
+            show_all_processes: false,
+            new_kill_rule: String::new(),
+            watch: Watch::default(),
+            new_block_rule: String::new(),
+            new_watch_hours: String::new(),
+            scheduled: Vec::new(),
+            scheduler: Scheduler::default(),
+            new_schedule: String::new(),
+            new_schedule_profile: String::new(),
+            new_override_name: String::new(),
+            snapshot_sweeps: true,
+            sessions: Sessions::default(),
+            sessions_path: None,
+            new_session_name: String::new(),
+            config_path: None,
+            written: Config::default(),
+            config_watcher: ConfigWatcher::default(),
+            config_touched: None,
+            config_problem: None,
+            layers: Layers::default(),
+            rule_set_path: String::new(),
+            reviewing: None,
+            rule_set_message: None,
+            undo_secs: 60,
+            undo_until: None,
+            undo_message: None,
+            undo_queued: false,
+            session_message: None,
+            countdown: None,
+            dry_run_killlist: false,
+            last_sweep: None,
+            sweep_rx: None,
+            tray: Tray::default(),
+            backend,
+        }
+    }
 
-                            ui.add_sized(
-                                [50.0, 20.0],
-                                egui::Label::new(strip_file_extension(name)),
-                            );
-                        });
-                    }
+    /// Looks at what's open right now, without the worker, for the command line.
+    pub fn refresh_processlist(&mut self) {
+        let list = enumerate(&mut self.sys, self.backend.as_ref());
+        self.use_processlist(list);
+    }
 
-                    if let Some(name) = to_remove {
-                        self.allowlist.remove(&name);
-                    }
-                });
+    /// Rebuilds both process lists from what we know about each process by pid.
+    pub fn update_processlists(&mut self, processes: &BTreeMap<u32, ProcessInfo>) {
+        let list = collect_processes(self.backend.as_ref(), processes, true);
+        self.use_processlist(list);
+    }
 
-            ui.separator();
-            ui.label("Tax Evaders:");
-            ui.label("These are closed whenever you please. Ctrl+Alt+J");
+    fn use_processlist(&mut self, unfiltered: ProcessList) {
+        // the unfiltered processlist, the killlist picks from there
+        self.unf_processlist = unfiltered;
+        self.filtered_with = None;
+        self.refilter();
+    }
 
-            // if ui.button("Close Notepad politely").clicked() {
-            //     close_by_pid(&24588).unwrap();
-            // }
+    // the filtered one out of it, when what's open, the lists or the classifier changed.
+    // comparing is cheap next to deciding on every process, this runs every frame
+    fn refilter(&mut self) {
+        let active = self.profiles.active();
+        if self
+            .filtered_with
+            .as_ref()
+            .is_some_and(|(profile, classifier)| {
+                profile == active && *classifier == self.classifier
+            })
+        {
+            return;
+        }
+        let (processlist, filtered) = self.sweepable(active);
+        self.processlist = processlist;
+        self.filtered = filtered;
+        self.filtered_with = Some((active.clone(), self.classifier.clone()));
+    }
 
-            if ui.button("Kill them all.").clicked() {
-                for (_, pid) in &self.processlist {
-                    close_by_pid(pid).unwrap();
-                }
-                for (name, pid) in &self.unf_processlist {
-                    if self.killlist.contains(name) {
-                        println!("hai: {}", name);
-                        close_by_pid(pid).unwrap();
+    // what a sweep with `profile` goes for (killlist aside), and what got filtered out and why
+    fn sweepable(&self, profile: &Profile) -> (ProcessList, BTreeMap<String, Filtered>) {
+        let mut list = ProcessList::new();
+        let mut filtered = BTreeMap::new();
+
+        for (name, processes) in &self.unf_processlist {
+            for (pid, process) in processes {
+                match decide(
+                    profile,
+                    &self.classifier,
+                    false,
+                    None,
+                    (name, *pid, process),
+                ) {
+                    Decision::Close => {
+                        list.entry(name.clone())
+                            .or_default()
+                            .insert(*pid, process.clone());
                     }
+                    Decision::Filtered(reason) => {
+                        filtered.entry(name.clone()).or_insert(reason);
+                    }
+                    _ => {}
                 }
             }
+        }
 
-            egui::ScrollArea::vertical()
-                .max_height(300.0)
-                .show(ui, |ui| {
-                    ui.set_width(ui.available_width());
-
-                    ui.horizontal(|ui| {
-                        ui.add_sized([50.0, 20.0], egui::Label::new("PID"));
-                        ui.add_sized([50.0, 20.0], egui::Label::new("Process Name"));
-                    });
+        (list, filtered)
+    }
 
-                    for (name, pid) in &self.processlist {
-                        ui.push_id(*pid, |ui| {
-                            ui.horizontal(|ui| {
-                                if ui.button("+").clicked() {
-                                    self.allowlist.insert(name.to_string());
-                                }
-                                ui.add_sized([50.0, 20.0], egui::Label::new(pid.to_string()));
-                                ui.add_sized(
-                                    [0.0, 20.0],
-                                    egui::Label::new(strip_file_extension(name)),
-                                );
-                            });
-                        });
-                    }
-                });
+    /// Every window worth mentioning and what a sweep with `profile` would do with it.
+    pub fn dry_run(
+        &self,
+        profile: &Profile,
+        with_killlist: bool,
+        spare: Option<u32>,
+    ) -> Vec<Candidate> {
+        let mut dry_run = Vec::new();
+        for (name, processes) in &self.unf_processlist {
+            for (pid, process) in processes {
+                let decision = decide(
+                    profile,
+                    &self.classifier,
+                    with_killlist,
+                    spare,
+                    (name, *pid, process),
+                );
+                dry_run.extend(candidates(name, *pid, process, &decision));
+            }
+        }
+        dry_run
+    }
 
-            // ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
-            // });
+    fn close_window(&self, window: WindowId) {
+        if let Err(e) = self.backend.close(window) {
+            log::warn!("Failed to close window {window}: {e}");
+        }
+    }
 
-            ui.separator();
+    /// What a sweep with `profile` closes, `spare` is a pid to leave alone.
+    pub fn sweep_targets(
+        &self,
+        profile: &Profile,
+        with_killlist: bool,
+        spare: Option<u32>,
+    ) -> ToClose {
+        sweep_targets(
+            &self.unf_processlist,
+            profile,
+            &self.classifier,
+            with_killlist,
+            spare,
+        )
+    }
 
-            ui.checkbox(&mut self.show_all_processes, "Advanced");
-            if !self.show_all_processes {
-                return;
+    /// Everything with a window that `rule` matches, apart from ourselves.
+    pub fn matching(&self, rule: &Rule) -> ToClose {
+        let mut to_close = ToClose::new();
+        for (name, processes) in &self.unf_processlist {
+            for (pid, process) in processes {
+                if !is_self(name) && *pid != std::process::id() && rule.matches(process) {
+                    to_close.push((name.clone(), *pid, process.clone()));
+                }
             }
-            ui.heading("advanced");
-            ui.separator();
+        }
+        to_close
+    }
 
-            ui.label("Explicit killlist");
-            ui.label("Here you can pick processes to kill if they do not appear up there, some (e.g. Figma) bypass my filters (for now).");
+    /// Closes everything in `to_close` with the strategies of `profile`, starting a new report.
+    pub fn close_list(&mut self, profile: &str, to_close: &ToClose) {
+        let Some(profile) = self.profiles.get(profile) else {
+            log::warn!("No profile called {profile:?}, not closing anything");
+            return;
+        };
 
-            egui::ScrollArea::vertical()
-                .max_height(300.0)
-                .id_salt("scrollin_killist_30x9403mcd2")
-                .show(ui, |ui| {
-                    ui.set_width(ui.available_width());
+        let (targets, results) = start_sweep(&self.backend, profile, to_close);
+        if self.snapshot_sweeps && !targets.is_empty() {
+            let apps = targets.iter().filter_map(|target| target.saved.clone());
+            self.sessions
+                .remember_sweep(self.sessions_path.as_deref(), apps.collect());
+        }
+        self.started_sweep(targets.len(), results, Instant::now());
+    }
 
-                    let mut to_remove = None;
+    // a sweep the worker did by itself, a while ago if we weren't painting
+    fn swept(&mut self, swept: Swept) {
+        if self.snapshot_sweeps && swept.targets > 0 {
+            match &self.sessions_path {
+                // it's in there already
+                Some(path) => self.sessions = Sessions::load(path),
+                None => self.sessions.remember_sweep(None, swept.saved),
+            }
+        }
+        self.started_sweep(swept.targets, swept.results, swept.at);
+    }
 
-                    for name in &self.killlist {
-                        ui.horizontal(|ui| {
-                            if ui.button("-").clicked() {
-                                to_remove = Some(name.clone());
-                            }
+    // a new report to fill in, and a while to undo it in
+    fn started_sweep(&mut self, targets: usize, results: Receiver<(Target, Outcome)>, at: Instant) {
+        self.undo_until = (targets > 0).then(|| at + Duration::from_secs(self.undo_secs));
+        self.undo_message = None;
+        self.undo_queued = false;
+        self.last_sweep = Some(SweepReport::new(targets));
+        self.sweep_rx = Some(results);
+        self.update_tooltip();
+    }
 
-                            ui.add_sized(
-                                [50.0, 20.0],
-                                egui::Label::new(strip_file_extension(name)),
-                            );
-                        });
-                    }
+    fn close_all(&mut self, profile: &str, with_killlist: bool, spare: Option<u32>) {
+        let Some(found) = self.profiles.get(profile) else {
+            log::warn!("No profile called {profile:?}, not sweeping");
+            return;
+        };
+        let list = self.sweep_targets(found, with_killlist, spare);
+        self.close_list(profile, &list);
+    }
 
-                    if let Some(name) = to_remove {
-                        self.killlist.remove(&name);
-                    }
-                });
+    /// What the hotkey does, every instance of everything in the processlist.
+    pub fn sweep(&mut self) {
+        let profile = self.profiles.active_name().to_owned();
+        self.sweep_with(&profile);
+    }
+
+    /// The same with another profile's lists, e.g. when its hotkey is pressed.
+    pub fn sweep_with(&mut self, profile: &str) {
+        self.close_all(profile, false, None);
+    }
 
+    fn focused_pid(&self) -> Option<u32> {
+        self.backend
+            .focused_window()
+            .and_then(|window| self.backend.owner_pid(window))
+    }
+
+    /// A sweep that leaves the app you're in open.
+    pub fn sweep_except_focused(&mut self) {
+        let profile = self.profiles.active_name().to_owned();
+        self.close_all(&profile, false, self.focused_pid());
+    }
+
+    // what the sweeping hotkeys do, after the profile's countdown if it has one.
+    // any of them again while it's counting down calls it off
+    fn hotkey_sweep(&mut self, profile: &str, spare: Option<u32>) {
+        if self.countdown.take().is_some() {
+            log::info!("Sweep cancelled");
+            return;
+        }
+        let Some(found) = self.profiles.get(profile) else {
+            log::warn!("No profile called {profile:?}, not sweeping");
+            return;
+        };
+        let to_close = self.sweep_targets(found, false, spare);
+        if found.countdown_secs == 0 || to_close.is_empty() {
+            self.close_list(profile, &to_close);
+            return;
+        }
+        let delay = Duration::from_secs(found.countdown_secs);
+        self.countdown = Some(Countdown::new(profile, to_close, delay));
+    }
 
-            ui.label("Note: System processes and most others will not be closed since the app does not kill them but asks to close the window instead.");
+    // counts down like the hotkey would, but leaves a countdown that's already going alone
+    fn scheduled_sweep(&mut self, profile: &str) {
+        if self.countdown.is_some() {
+            log::info!("Already counting down, skipping the scheduled sweep with {profile}");
+        } else {
+            self.hotkey_sweep(profile, None);
+        }
+        // there's another one next now
+        self.update_tooltip();
+    }
 
-            // populating unfiltered processlist
-            self.unf_processlist.clear();
-            for (pid, process) in self.sys.processes() {
-                let maybe_hwnd: Option<HWND> = get_hwnd_by_pid(pid.as_u32());
-                if maybe_hwnd.is_none() {
-                    continue;
+    fn end_countdown(&mut self, choice: Choice) {
+        match choice {
+            Choice::Wait => {}
+            Choice::Cancel => {
+                self.countdown = None;
+                log::info!("Sweep cancelled");
+            }
+            Choice::Confirm => {
+                if let Some(countdown) = self.countdown.take() {
+                    self.close_list(&countdown.profile, &countdown.to_close);
                 }
-                self.unf_processlist.insert(
-                    process.name().to_string_lossy().parse().unwrap(),
-                    pid.as_u32(),
-                );
-                if is_pseudo_open_in_taskbar(maybe_hwnd.unwrap(), true) {
-                    // we don't strip file extension at the source because we will use in the actual allowlist,
-                    // so it's removed only in display
-                    self.unf_processlist.insert(
-                        process.name().to_string_lossy().parse().unwrap(),
-                        pid.as_u32(),
-                    );
+            }
+        }
+    }
+
+    /// What the "Kill them all." button does, the sweep plus the killlist.
+    pub fn kill_them_all(&mut self) {
+        let profile = self.profiles.active_name().to_owned();
+        self.close_all(&profile, true, None);
+    }
+
+    /// The profile's lists to share, without the locked rules, those aren't the user's to
+    /// hand out. `None` if there's no such profile.
+    pub fn export_rules(&self, profile: &str) -> Option<RuleSet> {
+        let mut profiles = self.profiles.clone();
+        self.layers.strip(&mut profiles);
+        profiles.get(profile).map(RuleSet::export)
+    }
+
+    /// Merges `set` into the profile's lists, keeping ours where they disagree, or
+    /// replaces them with it, and saves. What it found, `None` if there's no such profile.
+    pub fn import_rules(
+        &mut self,
+        profile: &str,
+        set: &RuleSet,
+        replace: bool,
+    ) -> io::Result<Option<Merge>> {
+        let Some(profile) = self.profiles.get_mut(profile) else {
+            return Ok(None);
+        };
+        let merge = set.compare(profile);
+        if replace {
+            set.replace(profile);
+        } else {
+            merge.apply(profile);
+        }
+        self.layers.enforce(&mut self.profiles);
+        self.save_config()?;
+        Ok(Some(merge))
+    }
+
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
+    /// Relaunches a snapshot's apps, giving the ones that failed and why.
+    /// `None` if there's no such snapshot.
+    pub fn restore_session(&mut self, name: &str) -> Option<Vec<(String, io::Error)>> {
+        if let Some(path) = &self.sessions_path {
+            self.sessions = Sessions::load(path);
+        }
+        let failed = self
+            .sessions
+            .snapshots
+            .get(name)?
+            .restore()
+            .into_iter()
+            .map(|(app, e)| (app.name.clone(), e))
+            .collect();
+        Some(failed)
+    }
+
+    // restoring from the ui and the tray, saying how it went below the sessions
+    fn restore_from_ui(&mut self, name: &str) {
+        self.session_message = match self.restore_session(name) {
+            None => Some(format!("Nothing saved as {name}")),
+            Some(failed) if failed.is_empty() => None,
+            Some(failed) => Some(
+                failed
+                    .iter()
+                    .map(|(app, e)| format!("Couldn't start {app}: {e}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        };
+    }
+
+    fn closing(&self) -> bool {
+        self.last_sweep
+            .as_ref()
+            .is_some_and(|report| report.pending > 0)
+    }
+
+    /// Until a while after the sweep, which doesn't run out while it's still closing things.
+    pub fn can_undo(&self) -> bool {
+        self.undo_until
+            .is_some_and(|until| Instant::now() <= until || self.closing())
+    }
+
+    /// Starts everything the last sweep managed to close again, if it isn't too late.
+    /// Only once, what failed to start is in the error. Not while the sweep is still
+    /// closing things, we don't know what went away yet.
+    pub fn undo(&mut self) -> Result<usize, String> {
+        if self.can_undo() && self.closing() {
+            return Err("Still closing, undo once it's done".to_owned());
+        }
+        if !self.can_undo() {
+            return Err("Nothing to undo".to_owned());
+        }
+        self.relaunch()
+    }
+
+    // uses up the undo, whether or not it's still in time
+    fn relaunch(&mut self) -> Result<usize, String> {
+        self.undo_until = None;
+        let Some(report) = &self.last_sweep else {
+            return Err("Nothing to undo".to_owned());
+        };
+
+        let mut started = 0;
+        let mut failed = Vec::new();
+        for target in report.closed() {
+            let name = strip_file_extension(&target.name);
+            match target.saved.as_ref().map(SavedApp::launch) {
+                Some(Ok(())) => started += 1,
+                Some(Err(e)) => failed.push(format!("Couldn't start {name}: {e}")),
+                None => failed.push(format!("Don't know how to start {name}")),
+            }
+        }
+        if failed.is_empty() {
+            Ok(started)
+        } else {
+            Err(failed.join("\n"))
+        }
+    }
+
+    fn undo_from_ui(&mut self) {
+        if self.can_undo() && self.closing() {
+            self.undo_queued = true;
+            self.undo_message = Some("Undoing once the sweep is done".to_owned());
+            return;
+        }
+        let undone = self.undo();
+        self.show_undo(undone);
+    }
+
+    fn show_undo(&mut self, undone: Result<usize, String>) {
+        self.undo_message = match undone {
+            Ok(started) => Some(format!("Started {started} apps again")),
+            Err(e) => Some(e),
+        };
+    }
+
+    pub fn profiles(&self) -> &Profiles {
+        &self.profiles
+    }
+
+    pub fn switch_profile(&mut self, name: &str) {
+        if self.profiles.switch(name) {
+            self.tray.set_active_profile(name);
+        }
+    }
+
+    /// Waits for the escalation of the last sweep to settle, for when there's no frame to poll on.
+    pub fn finish_sweep(&mut self) -> Option<&SweepReport> {
+        if let (Some(rx), Some(report)) = (&self.sweep_rx, &mut self.last_sweep) {
+            while report.pending > 0 {
+                let Ok((target, outcome)) = rx.recv() else {
+                    break;
                 };
+                report.record(target, outcome);
+            }
+        }
+        self.last_sweep.as_ref()
+    }
+
+    // collecting whatever the escalation found out since last frame
+    fn poll_sweep(&mut self) {
+        let (Some(rx), Some(report)) = (&self.sweep_rx, &mut self.last_sweep) else {
+            return;
+        };
+        let mut changed = false;
+        while let Ok((target, outcome)) = rx.try_recv() {
+            report.record(target, outcome);
+            changed = true;
+        }
+        if changed {
+            self.update_tooltip();
+        }
+        // it was asked for in time, however long the sweep took
+        if self.undo_queued && !self.closing() {
+            self.undo_queued = false;
+            let undone = self.relaunch();
+            self.show_undo(undone);
+        }
+    }
+
+    fn update_tooltip(&self) {
+        self.tray
+            .set_tooltip(&self.tooltip(jiff::Zoned::now().datetime()));
+    }
+
+    // how the last sweep went and when the next one is
+    fn tooltip(&self, now: jiff::civil::DateTime) -> String {
+        let mut tooltip = match &self.last_sweep {
+            Some(report) => format!("expurgate: {}", report.summary()),
+            None => "expurgate".to_owned(),
+        };
+        if let Some((at, sweep)) = next_sweep(&self.scheduled, now) {
+            let day = if at.date() == now.date() { "" } else { "%a " };
+            tooltip.push_str(&format!(
+                "\nnext sweep at {} ({})",
+                at.strftime(&format!("{day}%H:%M")),
+                sweep.profile
+            ));
+        }
+        tooltip
+    }
+
+    fn report_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(report) = &self.last_sweep else {
+            return;
+        };
+
+        let mut dismiss = false;
+        let mut undo = false;
+        let undo_left = self
+            .undo_until
+            .map(|until| until.saturating_duration_since(Instant::now()).as_secs());
+        ui.horizontal(|ui| {
+            ui.label(format!("Last sweep: {}", report.summary()));
+            if let (true, Some(left)) = (self.can_undo(), undo_left) {
+                undo = ui
+                    .small_button("Undo")
+                    .on_hover_text(format!("Starts what it closed again, for {left} more s"))
+                    .clicked();
+                // so the button goes away on time
+                ui.ctx().request_repaint_after(Duration::from_secs(1));
+            }
+            if ui.small_button("x").clicked() {
+                dismiss = true;
             }
+        });
+        if let Some(message) = &self.undo_message {
+            ui.label(message);
+        }
+        for (target, outcome) in report.survivors() {
+            ui.label(format!(
+                "{} ({}): {}",
+                strip_file_extension(&target.name),
+                target.pid,
+                outcome.describe()
+            ));
+        }
+
+        if undo {
+            self.undo_from_ui();
+        }
+        if dismiss {
+            self.last_sweep = None;
+            self.sweep_rx = None;
+            self.undo_until = None;
+            self.undo_message = None;
+            self.undo_queued = false;
+            self.update_tooltip();
+        }
+    }
+
+    // every window and what the next sweep would do with it, without touching anything
+    fn dry_run_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Dry run").show(ui, |ui| {
+            ui.checkbox(
+                &mut self.dry_run_killlist,
+                "With the killlist, like the button",
+            );
+            let dry_run = self.dry_run(self.profiles.active(), self.dry_run_killlist, None);
+            let closing = dry_run
+                .iter()
+                .filter(|candidate| candidate.decision.closes())
+                .count();
+            ui.label(format!(
+                "{closing} of {} windows would be closed",
+                dry_run.len()
+            ));
 
             egui::ScrollArea::vertical()
-                .id_salt("cool-scrollarea-wahoo235235")
-                .max_height(301.0)
+                .id_salt("dry_run_scroll")
+                .max_height(200.0)
                 .show(ui, |ui| {
                     ui.set_width(ui.available_width());
+                    for candidate in &dry_run {
+                        let text = format!(
+                            "{} ({}) {}: {}",
+                            strip_file_extension(&candidate.name),
+                            candidate.pid,
+                            candidate.window.title,
+                            candidate.decision
+                        );
+                        if candidate.decision.closes() {
+                            ui.colored_label(ui.visuals().warn_fg_color, text);
+                        } else {
+                            ui.label(text);
+                        }
+                    }
+                });
+        });
+    }
 
-                    ui.horizontal(|ui| {
-                        ui.add_sized([50.0, 20.0], egui::Label::new("PID"));
-                        ui.add_sized([50.0, 20.0], egui::Label::new("Process Name"));
+    fn sessions_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Sessions").show(ui, |ui| {
+            ui.checkbox(&mut self.snapshot_sweeps, "Remember what each sweep closes");
+
+            let mut restore = None;
+            let mut remove = None;
+            for (name, snapshot) in &self.sessions.snapshots {
+                ui.horizontal(|ui| {
+                    if ui.button("-").clicked() {
+                        remove = Some(name.clone());
+                    }
+                    let titles = snapshot
+                        .apps
+                        .iter()
+                        .map(|app| {
+                            format!(
+                                "{}: {}",
+                                strip_file_extension(&app.name),
+                                app.titles.join(", ")
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    if ui.button("Restore").on_hover_text(titles).clicked() {
+                        restore = Some(name.clone());
+                    }
+                    ui.label(format!("{name} ({} apps)", snapshot.apps.len()));
+                });
+            }
+            if let Some(name) = restore {
+                self.restore_from_ui(&name);
+            }
+            if let Some(name) = remove {
+                self.sessions
+                    .update(self.sessions_path.as_deref(), |sessions| {
+                        sessions.snapshots.remove(&name);
                     });
+            }
 
-                    for (name, pid) in &self.unf_processlist {
-                        ui.push_id(*pid, |ui| {
-                            ui.horizontal(|ui| {
-                                if ui.button("+").clicked() {
-                                    self.killlist.insert(name.to_string());
-                                }
-                                ui.add_sized([50.0, 20.0], egui::Label::new(pid.to_string()));
-                                ui.add_sized(
-                                    [0.0, 20.0],
-                                    egui::Label::new(strip_file_extension(name)),
-                                );
-                            });
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_session_name)
+                    .on_hover_text("Keeps the last session under this name");
+                let name = self.new_session_name.trim().to_owned();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Keep"))
+                    .clicked()
+                {
+                    let mut kept = false;
+                    self.sessions
+                        .update(self.sessions_path.as_deref(), |sessions| {
+                            kept = sessions.keep_as(LAST_SESSION, &name);
                         });
+                    self.session_message = (!kept).then(|| "No sweep to keep yet".to_owned());
+                    self.new_session_name.clear();
+                }
+            });
+            if let Some(message) = &self.session_message {
+                ui.colored_label(ui.visuals().error_fg_color, message);
+            }
+        });
+    }
+
+    fn allowlist_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("allowlist");
+
+        ui.separator();
+
+        ui.label("allowlist in question:");
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .id_salt("scrollin_allowlist_30x9403mcd2")
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+
+                let profile = self.profiles.active_mut();
+                rule_rows(ui, &mut profile.allowlist, &profile.comments, &self.layers);
+            });
+        rule_input(
+            ui,
+            &mut self.profiles.active_mut().allowlist,
+            &mut self.new_allow_rule,
+        );
+    }
+
+    /// Switches to the profile after the active one, wrapping around.
+    pub fn next_profile(&mut self) {
+        let names = self.profiles.names().collect::<Vec<_>>();
+        let next = names
+            .iter()
+            .position(|name| *name == self.profiles.active_name())
+            .and_then(|i| names.get(i + 1).or(names.first()))
+            .map(|name| (*name).to_owned());
+        if let Some(name) = next {
+            self.switch_profile(&name);
+        }
+    }
+
+    fn hotkey_of(&self, binding: &Binding) -> Option<Hotkey> {
+        match binding {
+            Binding::Profile(name) => self.profiles.get(name).and_then(|profile| profile.hotkey),
+            Binding::Action(action) => self.hotkeys.get(action).copied(),
+        }
+    }
+
+    fn hotkey_bindings(&self) -> Vec<(Hotkey, HotkeyEvent)> {
+        let mut bindings = self.profiles.hotkey_bindings();
+        bindings.extend(
+            self.hotkeys
+                .iter()
+                .map(|(action, hotkey)| (*hotkey, HotkeyEvent::Action(*action))),
+        );
+        bindings
+    }
+
+    // whatever already has the hotkey, so we don't bind it twice
+    fn bound_to(&self, hotkey: Hotkey) -> Option<Binding> {
+        self.hotkey_bindings()
+            .into_iter()
+            .find(|(bound, _)| *bound == hotkey)
+            .map(|(_, event)| match event {
+                HotkeyEvent::Kill { profile } => Binding::Profile(profile),
+                HotkeyEvent::Action(action) => Binding::Action(action),
+            })
+    }
+
+    fn set_hotkey(&mut self, binding: &Binding, hotkey: Option<Hotkey>) -> Result<(), String> {
+        if let Some(hotkey) = hotkey {
+            match self.bound_to(hotkey) {
+                Some(other) if other != *binding => {
+                    return Err(format!("{hotkey} is already used for {}", other.label()));
+                }
+                _ => {}
+            }
+        }
+
+        match binding {
+            Binding::Profile(name) => {
+                let Some(profile) = self.profiles.get_mut(name) else {
+                    return Err(format!("no profile called {name}"));
+                };
+                profile.hotkey = hotkey;
+            }
+            Binding::Action(action) => match hotkey {
+                Some(hotkey) => {
+                    self.hotkeys.insert(*action, hotkey);
+                }
+                None => {
+                    self.hotkeys.remove(action);
+                }
+            },
+        }
+        self.apply_hotkeys();
+        Ok(())
+    }
+
+    // registers everything again, the desktop might take its time saying what's taken
+    fn apply_hotkeys(&mut self) {
+        let Some(listener) = &self.hotkey_listener else {
+            return;
+        };
+        if let Some(failures) = listener.rebind(self.hotkey_bindings()) {
+            self.hotkeys_bound(failures);
+        } else {
+            self.hotkeys_pending += 1;
+            self.hotkey_problems.clear();
+        }
+    }
+
+    // finds something free for whatever's taken
+    fn hotkeys_bound(&mut self, failures: Vec<(Hotkey, RegisterError)>) {
+        let Some(listener) = &self.hotkey_listener else {
+            return;
+        };
+        self.hotkey_problems = failures
+            .into_iter()
+            .map(|(hotkey, e)| {
+                let alternatives = if e == RegisterError::Taken {
+                    hotkey
+                        .alternatives()
+                        .into_iter()
+                        .filter(|alternative| self.bound_to(*alternative).is_none())
+                        .filter(|alternative| listener.is_free(*alternative))
+                        .take(3)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                (hotkey, (e, alternatives))
+            })
+            .collect();
+    }
+
+    // the next key combination pressed goes to whatever we're capturing for
+    fn capture_hotkey(&mut self, ctx: &egui::Context) {
+        let Some(binding) = self.capturing.clone() else {
+            return;
+        };
+        let pressed = ctx.input(|input| {
+            input.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    modifiers,
+                    pressed: true,
+                    ..
+                } => Some((*key, *modifiers)),
+                _ => None,
+            })
+        });
+        let Some((key, modifiers)) = pressed else {
+            return;
+        };
+
+        self.capturing = None;
+        if key == egui::Key::Escape {
+            return;
+        }
+        self.hotkey_message = captured_hotkey(modifiers, key)
+            .and_then(|hotkey| self.set_hotkey(&binding, Some(hotkey)))
+            .err();
+    }
+
+    fn hotkey_row(&mut self, ui: &mut egui::Ui, label: &str, binding: &Binding) {
+        let current = self.hotkey_of(binding);
+        let mut change = None;
+        ui.horizontal(|ui| {
+            ui.label(label);
+            let text = if self.capturing.as_ref() == Some(binding) {
+                "press a combination...".to_owned()
+            } else {
+                current.map_or_else(|| "none".to_owned(), |hotkey| hotkey.to_string())
+            };
+            if ui
+                .button(text)
+                .on_hover_text("Click, then press the new combination. Esc cancels.")
+                .clicked()
+            {
+                self.capturing = Some(binding.clone());
+            }
+            if current.is_some() && ui.small_button("x").clicked() {
+                change = Some(None);
+            }
+        });
+
+        if current.is_some() && self.hotkeys_pending > 0 {
+            ui.weak("waiting for the desktop to confirm...");
+        } else if let Some((e, alternatives)) =
+            current.and_then(|hotkey| self.hotkey_problems.get(&hotkey))
+        {
+            ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+            if !alternatives.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label("Free:");
+                    for alternative in alternatives {
+                        if ui.small_button(alternative.to_string()).clicked() {
+                            change = Some(Some(*alternative));
+                        }
+                    }
+                });
+            }
+        }
+
+        if let Some(hotkey) = change {
+            self.hotkey_message = self.set_hotkey(binding, hotkey).err();
+        }
+    }
+
+    fn hotkeys_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Hotkeys", |ui| {
+            for action in Action::ALL {
+                self.hotkey_row(ui, action.label(), &Binding::Action(action));
+            }
+        });
+        ui.separator();
+    }
+
+    fn profile_ui(&mut self, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        ui.horizontal(|ui| {
+            ui.label("Profile:");
+            egui::ComboBox::from_id_salt("profile")
+                .selected_text(self.profiles.active_name())
+                .show_ui(ui, |ui| {
+                    for name in self.profiles.names() {
+                        let active = name == self.profiles.active_name();
+                        if ui.selectable_label(active, name).clicked() {
+                            switch_to = Some(name.to_owned());
+                        }
+                    }
+                });
+            if ui
+                .button("-")
+                .on_hover_text("Delete this profile")
+                .clicked()
+            {
+                self.profiles.remove_active();
+                self.apply_hotkeys();
+                switch_to = Some(self.profiles.active_name().to_owned());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_profile_name)
+                .on_hover_text("New profile, starts as a copy of this one");
+            if ui.button("+").clicked() && self.profiles.add(self.new_profile_name.trim()) {
+                self.new_profile_name.clear();
+                switch_to = Some(self.profiles.active_name().to_owned());
+            }
+        });
+        if let Some(name) = switch_to {
+            self.switch_profile(&name);
+        }
+
+        let active = Binding::Profile(self.profiles.active_name().to_owned());
+        self.hotkey_row(ui, "Hotkey:", &active);
+        ui.horizontal(|ui| {
+            ui.label("Countdown:");
+            ui.add(egui::DragValue::new(&mut self.profiles.active_mut().countdown_secs).suffix(" s"))
+                .on_hover_text("Shows what the hotkey is about to close first. Esc or the hotkey again cancels, Enter closes right away. 0 closes right away.");
+        });
+        if let Some(message) = &self.hotkey_message {
+            ui.colored_label(ui.visuals().error_fg_color, message);
+        }
+        ui.separator();
+    }
+
+    fn activity_ui(&self, ui: &mut egui::Ui) {
+        if self.activity.is_empty() {
+            return;
+        }
+        ui.collapsing("Recent activity", |ui| {
+            for (at, event, process) in &self.activity {
+                let what = match event {
+                    WindowEvent::Opened(_) => "opened",
+                    _ => "closed",
+                };
+                ui.label(format!(
+                    "{} {process} {what} a window",
+                    at.strftime("%H:%M:%S")
+                ));
+            }
+        });
+    }
+
+    fn processlist_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Tax Evaders:");
+        match self.profiles.active().hotkey {
+            Some(hotkey) => ui.label(format!("These are closed whenever you please. {hotkey}")),
+            None => ui.label("These are closed whenever you please."),
+        };
+
+        // if ui.button("Close Notepad politely").clicked() {
+        //     close_by_pid(&24588).unwrap();
+        // }
+
+        if ui.button("Kill them all.").clicked() {
+            self.kill_them_all();
+        }
+
+        self.report_ui(ui);
+
+        self.dry_run_ui(ui);
+
+        self.sessions_ui(ui);
+
+        self.activity_ui(ui);
+
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+
+                let to_close = process_rows(
+                    ui,
+                    &self.processlist,
+                    &mut self.profiles.active_mut().allowlist,
+                );
+                for window in to_close {
+                    self.close_window(window);
+                }
+            });
+    }
+
+    fn strategy_ui(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        ui.label("When an app doesn't close:");
+        let profile = self.profiles.active_mut();
+        strategy_ui(ui, &mut profile.close_strategy);
+
+        ui.label("Per app:");
+        let mut to_remove = None;
+        for (name, strategy) in &mut profile.strategy_overrides {
+            ui.push_id(name, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("-").clicked() {
+                        to_remove = Some(name.clone());
                     }
+                    ui.label(strip_file_extension(name));
                 });
+                ui.indent("strategy", |ui| strategy_ui(ui, strategy));
+            });
+        }
+        if let Some(name) = to_remove {
+            profile.strategy_overrides.remove(&name);
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_override_name)
+                .on_hover_text("Process name, e.g. steam.exe");
+            if ui.button("+").clicked() && !self.new_override_name.is_empty() {
+                profile
+                    .strategy_overrides
+                    .entry(std::mem::take(&mut self.new_override_name))
+                    .or_insert_with(|| profile.close_strategy.clone());
+            }
         });
+        ui.separator();
+    }
+
+    fn classifier_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Hidden because they don't look like real apps:");
+        for (name, reason) in &self.filtered {
+            ui.label(format!("{} ({reason})", strip_file_extension(name)));
+        }
+
+        ui.collapsing("Filters", |ui| {
+            let mut toggled = None;
+            for rule in self.classifier.defaults() {
+                let mut enabled = self.classifier.is_enabled(rule);
+                if ui.checkbox(&mut enabled, rule.as_str()).changed() {
+                    toggled = Some((rule.clone(), enabled));
+                }
+            }
+
+            let mut to_remove = None;
+            for rule in &self.classifier.extra {
+                ui.horizontal(|ui| {
+                    if ui.button("-").clicked() {
+                        to_remove = Some(rule.clone());
+                    }
+                    let mut enabled = self.classifier.is_enabled(rule);
+                    if ui.checkbox(&mut enabled, rule.as_str()).changed() {
+                        toggled = Some((rule.clone(), enabled));
+                    }
+                });
+            }
+
+            if let Some((rule, enabled)) = toggled {
+                self.classifier.set_enabled(&rule, enabled);
+            }
+            if let Some(rule) = to_remove {
+                self.classifier.set_enabled(&rule, true);
+                self.classifier.extra.remove(&rule);
+            }
+            rule_input(
+                ui,
+                &mut self.classifier.extra,
+                &mut self.new_classifier_rule,
+            );
+        });
+        ui.separator();
+    }
+
+    fn watch_ui(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        ui.checkbox(&mut self.watch.enabled, "Watch mode").on_hover_text(
+            "Keeps the blocked apps closed, each one is asked to close once its window has been up for its grace period.",
+        );
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.watch.killlist, "The killlist too, after");
+            ui.add_enabled(
+                self.watch.killlist,
+                egui::DragValue::new(&mut self.watch.killlist_grace_secs).suffix(" s"),
+            );
+        });
+
+        ui.label("Blocked:");
+        let mut to_remove = None;
+        for (rule, grace) in &mut self.watch.blocked {
+            ui.horizontal(|ui| {
+                if ui.button("-").clicked() {
+                    to_remove = Some(rule.clone());
+                }
+                ui.label(rule_label(rule));
+                ui.add(egui::DragValue::new(grace).suffix(" s"))
+                    .on_hover_text("How long it gets before it's closed");
+            });
+        }
+        if let Some(rule) = to_remove {
+            self.watch.blocked.remove(&rule);
+        }
+        let mut added = BTreeSet::new();
+        rule_input(ui, &mut added, &mut self.new_block_rule);
+        for rule in added {
+            self.watch.blocked.entry(rule).or_insert(10);
+        }
+
+        ui.label("Only during, always if there's nothing here:");
+        let mut to_remove = None;
+        for (i, hours) in self.watch.hours.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button("-").clicked() {
+                    to_remove = Some(i);
+                }
+                ui.label(hours.to_string());
+            });
+        }
+        if let Some(i) = to_remove {
+            self.watch.hours.remove(i);
+        }
+        let parsed = self.new_watch_hours.parse::<Hours>();
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_watch_hours)
+                .on_hover_text("e.g. Mon-Fri 09:00-17:30, or 22:00-02:00 for every night");
+            if ui
+                .add_enabled(parsed.is_ok(), egui::Button::new("+"))
+                .clicked()
+            {
+                if let Ok(hours) = &parsed {
+                    self.watch.hours.push(hours.clone());
+                    self.new_watch_hours.clear();
+                }
+            }
+        });
+        if let (Err(e), false) = (&parsed, self.new_watch_hours.trim().is_empty()) {
+            ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+        }
+    }
+
+    // what the buttons in rule_sets_ui do, `review` holds on to the conflicts
+    fn import_from_ui(&mut self, replace: bool, review: bool) {
+        let path = PathBuf::from(self.rule_set_path.trim());
+        let set = match RuleSet::load(&path) {
+            Ok(set) => set,
+            Err(e) => {
+                self.rule_set_message = Some(format!("Failed to read {}: {e}", path.display()));
+                return;
+            }
+        };
+        let name = self.profiles.active_name().to_owned();
+        let merge = set.compare(self.profiles.active());
+        if review && !merge.conflicts.is_empty() {
+            self.rule_set_message = Some(format!(
+                "{} new, {} to go through",
+                merge.added.len(),
+                merge.conflicts.len()
+            ));
+            self.reviewing = Some((name, merge));
+            return;
+        }
+        self.rule_set_message = match self.import_rules(&name, &set, replace) {
+            Ok(Some(_)) if replace => Some(format!("Replaced the lists with {}", path.display())),
+            Ok(Some(merge)) => Some(format!(
+                "{} new, kept yours for {}",
+                merge.added.len(),
+                merge.conflicts.len()
+            )),
+            Ok(None) => None,
+            Err(e) => Some(format!("Imported, but couldn't save it: {e}")),
+        };
+    }
+
+    fn rule_sets_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Rule sets").show(ui, |ui| {
+            ui.label("This profile's allowlist and killlist in a file of their own, comments and all, to share.");
+            ui.add(egui::TextEdit::singleline(&mut self.rule_set_path).hint_text("team-rules.toml"));
+            let path = PathBuf::from(self.rule_set_path.trim());
+            ui.horizontal(|ui| {
+                if ui.button("Export").clicked() {
+                    if let Some(set) = self.export_rules(self.profiles.active_name()) {
+                        self.rule_set_message = Some(match set.save(&path) {
+                            Ok(()) => format!("Exported to {}", path.display()),
+                            Err(e) => format!("Failed to export: {e}"),
+                        });
+                    }
+                }
+                if ui
+                    .button("Import")
+                    .on_hover_text("Adds what's new, keeps yours where the two disagree")
+                    .clicked()
+                {
+                    self.import_from_ui(false, false);
+                }
+                if ui
+                    .button("Import and review")
+                    .on_hover_text("Adds what's new, and lets you pick where the two disagree")
+                    .clicked()
+                {
+                    self.import_from_ui(false, true);
+                }
+                if ui
+                    .button("Replace")
+                    .on_hover_text("Makes the lists exactly what's in the file")
+                    .clicked()
+                {
+                    self.import_from_ui(true, false);
+                }
+            });
+            if let Some(message) = &self.rule_set_message {
+                ui.label(message);
+            }
+
+            let mut done = None;
+            if let Some((name, merge)) = &mut self.reviewing {
+                for conflict in &mut merge.conflicts {
+                    ui.horizontal(|ui| {
+                        ui.label(rule_label(&conflict.rule));
+                        ui.radio_value(
+                            &mut conflict.take_theirs,
+                            false,
+                            format!("yours: {}", conflict.ours),
+                        );
+                        ui.radio_value(
+                            &mut conflict.take_theirs,
+                            true,
+                            format!("theirs: {}", conflict.theirs),
+                        );
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Apply").clicked() {
+                        done = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = Some(false);
+                    }
+                });
+                if done == Some(true) {
+                    if let Some(profile) = self.profiles.get_mut(name) {
+                        merge.apply(profile);
+                    }
+                }
+            }
+            if let Some(applied) = done {
+                self.reviewing = None;
+                self.rule_set_message = Some(if applied { "Merged" } else { "Left as it was" }.to_owned());
+            }
+        });
+    }
+
+    fn schedule_ui(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Schedule").show(ui, |ui| {
+            let mut changed = false;
+            let mut remove = None;
+            for (i, sweep) in self.scheduled.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.button("-").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.label(format!("{}: {}", sweep.when, sweep.profile));
+                });
+            }
+            if let Some(i) = remove {
+                self.scheduled.remove(i);
+                changed = true;
+            }
+
+            if self.profiles.get(&self.new_schedule_profile).is_none() {
+                self.new_schedule_profile = self.profiles.active_name().to_owned();
+            }
+            let parsed = self.new_schedule.parse::<When>();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_schedule)
+                    .on_hover_text("e.g. Mon-Fri 18:30, or idle 15 for once nobody's been around for 15 minutes");
+                egui::ComboBox::from_id_salt("schedule_profile")
+                    .selected_text(&self.new_schedule_profile)
+                    .show_ui(ui, |ui| {
+                        for name in self.profiles.names() {
+                            ui.selectable_value(
+                                &mut self.new_schedule_profile,
+                                name.to_owned(),
+                                name,
+                            );
+                        }
+                    });
+                if ui
+                    .add_enabled(parsed.is_ok(), egui::Button::new("+"))
+                    .clicked()
+                {
+                    if let Ok(when) = &parsed {
+                        self.scheduled.push(ScheduledSweep {
+                            when: when.clone(),
+                            profile: self.new_schedule_profile.clone(),
+                        });
+                        self.new_schedule.clear();
+                        changed = true;
+                    }
+                }
+            });
+            if let (Err(e), false) = (&parsed, self.new_schedule.trim().is_empty()) {
+                ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+            }
+            if let Some((at, sweep)) = next_sweep(&self.scheduled, jiff::Zoned::now().datetime())
+            {
+                ui.label(format!(
+                    "Next: {} with {}",
+                    at.strftime("%a %H:%M"),
+                    sweep.profile
+                ));
+            }
+
+            if changed {
+                self.scheduler.set(self.scheduled.clone());
+                self.update_tooltip();
+            }
+        });
+    }
+
+    fn config_ui(&self, ui: &mut egui::Ui) {
+        let Some(path) = &self.config_path else {
+            return;
+        };
+        ui.separator();
+        ui.label(format!("Settings are kept in {}", path.display()))
+            .on_hover_text("Edit it however you like, changes show up here right away.");
+        if let Some(problem) = &self.config_problem {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!("It doesn't read, so nothing gets saved over it until it does: {problem}"),
+            );
+        }
+    }
+
+    fn advanced_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("advanced");
+        ui.separator();
+
+        ui.label("Explicit killlist");
+        ui.label("Here you can pick processes to kill if they do not appear up there, some (e.g. Figma) bypass my filters (for now).");
+
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .id_salt("scrollin_killist_30x9403mcd2")
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+
+                let profile = self.profiles.active_mut();
+                rule_rows(ui, &mut profile.killlist, &profile.comments, &self.layers);
+            });
+        rule_input(
+            ui,
+            &mut self.profiles.active_mut().killlist,
+            &mut self.new_kill_rule,
+        );
+
+        ui.label("Note: System processes and most others will not be closed since the app does not kill them but asks to close the window instead.");
+
+        self.watch_ui(ui);
+
+        self.schedule_ui(ui);
+
+        self.strategy_ui(ui);
+
+        self.classifier_ui(ui);
+
+        self.hotkeys_ui(ui);
+
+        self.rule_sets_ui(ui);
+
+        self.config_ui(ui);
+
+        egui::ScrollArea::vertical()
+            .id_salt("cool-scrollarea-wahoo235235")
+            .max_height(301.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+
+                let to_close = process_rows(
+                    ui,
+                    &self.unf_processlist,
+                    &mut self.profiles.active_mut().killlist,
+                );
+                for window in to_close {
+                    self.close_window(window);
+                }
+            });
+    }
+}
+
+impl Expurgate {
+    // whatever the worker left to us
+    fn on_hotkey(&mut self, ctx: &egui::Context, e: HotkeyEvent) {
+        match e {
+            HotkeyEvent::Kill { profile } => {
+                println!("Polite murder initiated ({profile}).");
+                self.hotkey_sweep(&profile, None);
+            }
+            HotkeyEvent::Action(Action::Sweep) => {
+                let profile = self.profiles.active_name().to_owned();
+                self.hotkey_sweep(&profile, None);
+            }
+            HotkeyEvent::Action(Action::SweepExceptFocused) => {
+                let profile = self.profiles.active_name().to_owned();
+                self.hotkey_sweep(&profile, self.focused_pid());
+            }
+            HotkeyEvent::Action(Action::ShowWindow) => tray::show_window(ctx),
+            HotkeyEvent::Action(Action::NextProfile) => self.next_profile(),
+            HotkeyEvent::Action(Action::Undo) => self.undo_from_ui(),
+        }
+    }
+}
+
+impl eframe::App for Expurgate {
+    /// Called by the framework to save state before shutdown.
+    fn save(&mut self, _storage: &mut dyn eframe::Storage) {
+        // everything worth keeping is in the config file, eframe only keeps the window size
+        if let Err(e) = self.save_config() {
+            log::warn!("Failed to save the config: {e}");
+        }
+    }
+
+    /// Called each time the UI needs repainting, which may be many times per second.
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
+        // For inspiration and more examples, go to https://emilk.github.io/egui
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            // The top panel is often a good place for a menu bar:
+
+            egui::MenuBar::new().ui(ui, |ui| {
+                egui::widgets::global_theme_preference_buttons(ui);
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // The central panel the region left after adding TopPanel's and SidePanel's
+            while let Some(update) = self.worker.try_recv() {
+                match update {
+                    Update::Processes(list) => {
+                        self.unf_processlist = list;
+                        self.filtered_with = None;
+                    }
+                    Update::Swept(swept) => self.swept(swept),
+                    Update::Hotkey(e) => self.on_hotkey(ctx, e),
+                    Update::Scheduled(profile) => self.scheduled_sweep(&profile),
+                    Update::Bound(failures) => {
+                        self.hotkeys_pending = self.hotkeys_pending.saturating_sub(1);
+                        if self.hotkeys_pending == 0 {
+                            self.hotkeys_bound(failures);
+                        }
+                    }
+                    Update::Window { event, pid, name } => {
+                        self.activity.push_front((
+                            jiff::Zoned::now(),
+                            event,
+                            format!("{name} ({pid})"),
+                        ));
+                        self.activity.truncate(ACTIVITY);
+                    }
+                }
+            }
+            // the lists might've changed since
+            self.refilter();
+            self.poll_sweep();
+            if self
+                .last_sweep
+                .as_ref()
+                .is_some_and(|report| report.pending > 0)
+            {
+                // results come in from another thread
+                ctx.request_repaint_after(Duration::from_millis(500));
+            }
+
+            self.capture_hotkey(ctx);
+            if let Some(countdown) = &mut self.countdown {
+                let choice = countdown.show(ctx);
+                self.end_countdown(choice);
+            }
+
+            while let Some(e) = self.tray.try_recv() {
+                match e {
+                    TrayEvent::SwitchProfile(name) => self.switch_profile(&name),
+                    TrayEvent::RestoreLastSession => self.restore_from_ui(LAST_SESSION),
+                    TrayEvent::Undo => self.undo_from_ui(),
+                }
+            }
+
+            while let Some(reloaded) = self.config_watcher.try_recv() {
+                self.reload_config(reloaded);
+            }
+
+            // ui:
+
+            self.profile_ui(ui);
+
+            self.allowlist_ui(ui);
+
+            ui.separator();
+
+            self.processlist_ui(ui);
+
+            // ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
+            // });
+
+            ui.separator();
+
+            ui.checkbox(&mut self.show_all_processes, "Advanced");
+            if !self.show_all_processes {
+                return;
+            }
+
+            self.advanced_ui(ui);
+        });
+
+        // a new profile or a replaced list gets the locked rules back
+        self.layers.enforce(&mut self.profiles);
+        // whatever changed this frame, for the next hotkey
+        self.sync_worker();
+        self.save_config_when_settled(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{Binding, Expurgate, SETTLE, captured_hotkey};
+    use crate::backend::mock::{MockBackend, MockWindow};
+    use crate::close::{CloseStrategy, Outcome, SweepReport, Target};
+    use crate::config::Config;
+    use crate::countdown::Choice;
+    use crate::hotkey::{Action, Hotkey};
+    use crate::policy::Layers;
+    use crate::processes::{ProcessInfo, close_targets, collect_processes};
+    use crate::profiles::DEFAULT_PROFILE;
+    use crate::rules::Rule;
+    use crate::ruleset::{Entry, RuleSet};
+    use crate::scheduler::ScheduledSweep;
+    use crate::session::SavedApp;
+
+    #[test]
+    fn only_taskbar_windows_are_collected() {
+        let mock = MockBackend::new()
+            .process(1, "plain.exe")
+            .window(MockWindow::new(10, 1))
+            .process(2, "hidden.exe")
+            .window(MockWindow::new(20, 2).hidden())
+            .process(3, "cloaked.exe")
+            .window(MockWindow::new(30, 3).cloaked())
+            .process(4, "tool.exe")
+            .window(MockWindow::new(40, 4).toolwindow())
+            .process(5, "nowindow.exe");
+
+        let list = collect_processes(&mock, mock.processes(), false);
+        assert_eq!(
+            list.keys().collect::<Vec<_>>(),
+            ["plain.exe"],
+            "only the plain window is in the taskbar"
+        );
+
+        let all = collect_processes(&mock, mock.processes(), true);
+        assert_eq!(all.len(), 4, "show all still needs a window");
+    }
+
+    #[test]
+    fn instances_are_grouped_by_executable() {
+        let mock = MockBackend::new()
+            .process(1, "notepad.exe")
+            .window(MockWindow::new(10, 1))
+            .window(MockWindow::new(11, 1).hidden())
+            .process(2, "notepad.exe")
+            .window(MockWindow::new(20, 2))
+            .process(3, "notepad.exe")
+            .window(MockWindow::new(30, 3));
+
+        let list = collect_processes(&mock, mock.processes(), false);
+        let notepads = &list["notepad.exe"];
+        assert_eq!(notepads.len(), 3, "all three instances are there");
+        assert_eq!(
+            notepads[&1]
+                .windows
+                .iter()
+                .map(|w| w.id)
+                .collect::<Vec<_>>(),
+            [10, 11],
+            "with every window they have"
+        );
+    }
+
+    #[test]
+    fn only_the_windows_you_see_are_closed() {
+        let mock = MockBackend::new()
+            .process(1, "files.exe")
+            .window(MockWindow::new(10, 1).title("Downloads"))
+            .window(MockWindow::new(11, 1).hidden())
+            .window(MockWindow::new(12, 1).title("Documents"))
+            .window(MockWindow::new(13, 1).toolwindow());
+        let (mut app, mock) = mock.app();
+
+        app.sweep();
+        assert_eq!(
+            mock.closed(),
+            [10, 12],
+            "hidden and tool windows are left alone"
+        );
+    }
+
+    #[test]
+    fn close_targets_fall_back_for_windowless_apps() {
+        let mock = MockBackend::new()
+            .process(1, "tool.exe")
+            .window(MockWindow::new(10, 1).toolwindow())
+            .window(MockWindow::new(11, 1).hidden())
+            .process(2, "ghost.exe")
+            .window(MockWindow::new(20, 2).hidden());
+        let list = collect_processes(&mock, mock.processes(), true);
+
+        assert_eq!(
+            close_targets(&list["tool.exe"][&1].windows),
+            [10],
+            "visible ones when nothing is in the taskbar"
+        );
+        assert_eq!(
+            close_targets(&list["ghost.exe"][&2].windows),
+            [20],
+            "everything when nothing is visible"
+        );
+    }
+
+    #[test]
+    fn sweep_closes_every_instance() {
+        let mock = MockBackend::new()
+            .process(1, "notepad.exe")
+            .window(MockWindow::new(10, 1))
+            .process(2, "notepad.exe")
+            .window(MockWindow::new(20, 2))
+            .process(3, "notepad.exe")
+            .window(MockWindow::new(30, 3));
+        let (mut app, mock) = mock.app();
+
+        app.sweep();
+        assert_eq!(mock.closed(), [10, 20, 30], "not just the last notepad");
+    }
+
+    #[test]
+    fn visible_popup_of_a_hidden_owner_counts() {
+        // e.g. an installer, the real window is the popup
+        let mock = MockBackend::new()
+            .process(1, "setup.exe")
+            .window(MockWindow::new(10, 1).hidden())
+            .window(MockWindow::new(11, 1).owned_by(10));
+
+        let list = collect_processes(&mock, mock.processes(), false);
+        assert!(list.contains_key("setup.exe"), "the popup makes it count");
+    }
+
+    #[test]
+    fn processlist_is_filtered() {
+        let (app, _) = MockBackend::desk().app();
+        assert_eq!(
+            app.processlist.keys().collect::<Vec<_>>(),
+            ["code.exe", "notepad.exe"],
+            "helpers, ourselves and tool windows are gone"
+        );
+    }
+
+    #[test]
+    fn allowlisted_processes_are_removed() {
+        let mock = Arc::new(MockBackend::desk());
+        let mut app = Expurgate::with_backend(mock.clone());
+        app.profiles
+            .active_mut()
+            .allowlist
+            .insert(Rule::name("code.exe"));
+        app.update_processlists(mock.processes());
+
+        assert!(
+            !app.processlist.contains_key("code.exe"),
+            "code is allowlisted"
+        );
+        assert!(app.processlist.contains_key("notepad.exe"), "notepad isn't");
+
+        app.sweep();
+        assert_eq!(mock.closed(), [2], "only notepad gets closed");
+    }
+
+    #[test]
+    fn filtering_is_only_redone_when_the_lists_change() {
+        let (mut app, _) = MockBackend::desk().app();
+        assert!(app.processlist.contains_key("code.exe"), "code goes");
+
+        app.processlist.clear();
+        app.refilter();
+        assert!(
+            app.processlist.is_empty(),
+            "nothing changed, nothing redone"
+        );
+
+        app.profiles
+            .active_mut()
+            .allowlist
+            .insert(Rule::name("code.exe"));
+        app.refilter();
+        assert!(
+            app.processlist.contains_key("notepad.exe")
+                && !app.processlist.contains_key("code.exe"),
+            "redone for the new allowlist"
+        );
+    }
+
+    #[test]
+    fn expurgate_does_not_close_itself() {
+        let (mut app, mock) = MockBackend::desk().app();
+        assert!(
+            !app.processlist.contains_key("expurgate.exe"),
+            "we're not in the list"
+        );
+
+        app.kill_them_all();
+        assert!(!mock.closed().contains(&4), "we didn't close ourselves");
+    }
+
+    #[test]
+    fn own_pid_is_excluded_whatever_the_name() {
+        let me = std::process::id();
+        let mock = MockBackend::new()
+            .process(me, "renamed.exe")
+            .window(MockWindow::new(1, me));
+        let (app, _) = mock.app();
+        assert!(app.processlist.is_empty(), "we're excluded by pid too");
+    }
+
+    #[test]
+    fn kill_them_all_closes_the_killlist_too() {
+        let mock = Arc::new(MockBackend::desk());
+        let mut app = Expurgate::with_backend(mock.clone());
+        app.profiles
+            .active_mut()
+            .killlist
+            .insert(Rule::name("Figma.exe"));
+        app.update_processlists(mock.processes());
+
+        app.sweep();
+        assert_eq!(
+            mock.closed(),
+            [1, 2],
+            "the hotkey leaves the killlist alone"
+        );
+
+        app.kill_them_all();
+        assert_eq!(
+            mock.closed(),
+            [1, 2, 1, 2, 5],
+            "the button also closes figma, which the filters miss"
+        );
+    }
+
+    #[test]
+    fn allowlist_patterns_match_titles() {
+        let mock = Arc::new(MockBackend::desk());
+        let mut app = Expurgate::with_backend(mock.clone());
+        app.profiles
+            .active_mut()
+            .allowlist
+            .insert(r#"title~=".*- Visual Studio Code$""#.parse().expect("valid rule"));
+        app.update_processlists(mock.processes());
+
+        assert_eq!(
+            app.processlist.keys().collect::<Vec<_>>(),
+            ["notepad.exe"],
+            "code is allowlisted by its title"
+        );
+    }
+
+    #[test]
+    fn killlist_patterns_match_paths() {
+        let mock = Arc::new(
+            MockBackend::desk()
+                .process_info(
+                    600,
+                    ProcessInfo {
+                        exe: Some("/opt/jetbrains/idea/bin/fsnotifier".into()),
+                        ..ProcessInfo::new("fsnotifier")
+                    },
+                )
+                .window(MockWindow::new(6, 600).hidden()),
+        );
+        let mut app = Expurgate::with_backend(mock.clone());
+        app.profiles
+            .active_mut()
+            .killlist
+            .insert("path=/opt/jetbrains/**".parse().expect("valid rule"));
+        app.update_processlists(mock.processes());
+
+        app.kill_them_all();
+        assert_eq!(
+            mock.closed(),
+            [1, 2, 6],
+            "everything from the jetbrains folder goes too"
+        );
+    }
+
+    #[test]
+    fn filtered_processes_say_why() {
+        let (mut app, mock) = MockBackend::desk().app();
+        assert_eq!(
+            app.filtered
+                .get("SearchHost.exe")
+                .map(ToString::to_string)
+                .as_deref(),
+            Some("name=*host*"),
+            "searchhost is hidden by the host rule"
+        );
+
+        app.classifier
+            .set_enabled(&"name=*host*".parse().expect("valid rule"), false);
+        app.update_processlists(mock.processes());
+        assert!(
+            app.processlist.contains_key("SearchHost.exe"),
+            "and comes back once that's off"
+        );
+    }
+
+    #[test]
+    fn hotkey_sweeps_use_their_own_profile() {
+        let (mut app, mock) = MockBackend::desk().app();
+        app.profiles.add("work");
+        app.profiles
+            .active_mut()
+            .allowlist
+            .insert(Rule::name("code.exe"));
+        app.switch_profile(DEFAULT_PROFILE);
+
+        app.sweep_with("work");
+        assert_eq!(mock.closed(), [2], "work keeps code open");
+
+        app.sweep();
+        assert_eq!(mock.closed(), [2, 1, 2], "the default profile doesn't");
+    }
+
+    #[test]
+    fn lists_from_before_profiles_are_kept() {
+        let mut app = Expurgate::with_backend(Arc::new(MockBackend::desk()));
+        app.allowlist = Some([Rule::name("code.exe")].into());
+        app.migrate();
+
+        assert!(
+            app.profiles
+                .active()
+                .allowlist
+                .contains(&Rule::name("code.exe")),
+            "moved into the default profile"
+        );
+        assert_eq!(app.allowlist, None, "and only once");
+    }
+
+    #[test]
+    fn focused_app_survives_its_sweep() {
+        let (mut app, mock) = MockBackend::desk().focused(1).app();
+        app.sweep_except_focused();
+        assert_eq!(mock.closed(), [2], "code has focus");
+    }
+
+    #[test]
+    fn countdowns_hold_hotkey_sweeps_back() {
+        let (mut app, mock) = MockBackend::desk().app();
+        app.profiles.active_mut().countdown_secs = 3;
+
+        app.hotkey_sweep(DEFAULT_PROFILE, None);
+        assert!(mock.closed().is_empty(), "counting down first");
+        app.hotkey_sweep(DEFAULT_PROFILE, None);
+        assert!(app.countdown.is_none(), "the hotkey again cancels");
+        assert!(mock.closed().is_empty(), "and nothing was closed");
+
+        app.hotkey_sweep(DEFAULT_PROFILE, None);
+        app.end_countdown(Choice::Confirm);
+        assert_eq!(mock.closed(), [1, 2], "enter closes right away");
+    }
+
+    fn closed_gedit() -> Target {
+        let saved = SavedApp {
+            name: "gedit".to_owned(),
+            exe: "/nonexistent/gedit".into(),
+            args: Vec::new(),
+            cwd: None,
+            titles: Vec::new(),
+        };
+        Target {
+            name: "gedit".to_owned(),
+            pid: 600,
+            start_time: 0,
+            strategy: CloseStrategy::default(),
+            windows: vec![6],
+            saved: Some(saved),
+        }
+    }
+
+    #[test]
+    fn undo_is_once_and_only_for_a_while() {
+        let (mut app, _) = MockBackend::desk().app();
+        let target = closed_gedit();
+        let mut report = SweepReport::new(2);
+        report.record(target.clone(), Outcome::Closed);
+        report.record(target, Outcome::WaitingOnDialog);
+        app.last_sweep = Some(report);
+
+        app.undo_until = Instant::now().checked_sub(Duration::from_secs(1));
+        assert_eq!(app.undo(), Err("Nothing to undo".to_owned()), "too late");
+
+        app.undo_until = Some(Instant::now() + Duration::from_secs(60));
+        let failed = app.undo().expect_err("nothing to launch in the test");
+        assert_eq!(
+            failed.lines().count(),
+            1,
+            "only what closed is started again"
+        );
+        assert!(!app.can_undo(), "and only once");
+    }
+
+    #[test]
+    fn undo_waits_for_the_sweep_to_finish() {
+        let (mut app, _) = MockBackend::desk().app();
+        app.undo_secs = 0;
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.started_sweep(1, rx, Instant::now());
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert!(app.can_undo(), "doesn't run out during the grace period");
+        assert!(app.undo().is_err(), "nothing's closed yet");
+        assert!(app.can_undo(), "so it's not used up");
+
+        app.undo_from_ui();
+        assert!(app.undo_queued, "it goes once the results are in");
+        tx.send((closed_gedit(), Outcome::Closed))
+            .expect("the app's listening");
+        app.poll_sweep();
+        assert!(!app.undo_queued, "and it went");
+        assert!(
+            app.undo_message
+                .as_deref()
+                .is_some_and(|message| message.starts_with("Couldn't start gedit")),
+            "starting what closed: {:?}",
+            app.undo_message
+        );
+        assert!(!app.can_undo(), "only once");
+    }
+
+    #[test]
+    fn tooltip_says_when_the_next_sweep_is() {
+        let (mut app, _) = MockBackend::desk().app();
+        // 2026-10-23 is a friday
+        let friday = jiff::civil::date(2026, 10, 23);
+        assert_eq!(
+            app.tooltip(friday.at(12, 0, 0, 0)),
+            "expurgate",
+            "nothing scheduled"
+        );
+
+        app.scheduled.push(ScheduledSweep {
+            when: "Mon-Fri 18:30".parse().expect("valid schedule"),
+            profile: "end of day".to_owned(),
+        });
+        assert_eq!(
+            app.tooltip(friday.at(12, 0, 0, 0)),
+            "expurgate\nnext sweep at 18:30 (end of day)",
+            "later today"
+        );
+        assert_eq!(
+            app.tooltip(friday.at(19, 0, 0, 0)),
+            "expurgate\nnext sweep at Mon 18:30 (end of day)",
+            "after the weekend"
+        );
+    }
+
+    #[test]
+    fn next_profile_wraps_around() {
+        let (mut app, _) = MockBackend::desk().app();
+        app.profiles.add("work");
+        app.next_profile();
+        assert_eq!(
+            app.profiles.active_name(),
+            crate::profiles::DEFAULT_PROFILE,
+            "work is last, so back to default"
+        );
+        app.next_profile();
+        assert_eq!(app.profiles.active_name(), "work", "and on to work");
+    }
+
+    #[test]
+    fn captured_keys_become_hotkeys() {
+        let ctrl_alt = egui::Modifiers {
+            alt: true,
+            ctrl: true,
+            command: true,
+            ..egui::Modifiers::NONE
+        };
+        assert_eq!(
+            captured_hotkey(ctrl_alt, egui::Key::E),
+            "Ctrl+Alt+E".parse(),
+            "command is only ctrl here"
+        );
+        let logo = egui::Modifiers {
+            shift: true,
+            command: true,
+            mac_cmd: true,
+            ..egui::Modifiers::NONE
+        };
+        assert_eq!(
+            captured_hotkey(logo, egui::Key::F5),
+            "Shift+Super+F5".parse(),
+            "the cmd key is super"
+        );
+    }
+
+    #[test]
+    fn a_hotkey_is_only_bound_once() {
+        let (mut app, _) = MockBackend::desk().app();
+        let show = Binding::Action(Action::ShowWindow);
+        assert!(
+            app.set_hotkey(&show, Some(Hotkey::DEFAULT)).is_err(),
+            "the default profile has Ctrl+Alt+J"
+        );
+
+        let free = "Ctrl+Alt+S".parse().ok();
+        assert_eq!(app.set_hotkey(&show, free), Ok(()), "nobody has Ctrl+Alt+S");
+        assert_eq!(
+            app.hotkey_bindings().len(),
+            2,
+            "the profile and show window"
+        );
+    }
+
+    #[test]
+    fn config_file_edits_reach_the_app() {
+        let (mut app, _) = MockBackend::desk().app();
+        assert_eq!(
+            app.config(),
+            Config::default(),
+            "a fresh app is a fresh config"
+        );
+
+        let mut edited = Config {
+            undo_secs: 5,
+            ..Config::default()
+        };
+        edited
+            .profiles
+            .active_mut()
+            .allowlist
+            .insert(Rule::name("code.exe"));
+        app.reload_config(Ok(edited.clone()));
+        assert_eq!(app.config(), edited, "someone else's edit");
+        assert!(!app.config_changed(), "nothing to write back");
+    }
+
+    #[test]
+    fn imports_that_dont_stick_say_so() {
+        let dir = std::env::temp_dir().join(format!("expurgate-unsaved-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let (mut app, _) = MockBackend::desk().app();
+        app.config_path = Some(path.clone());
+        app.config_problem = Some("version 99 is newer than this expurgate knows".to_owned());
+
+        let set = RuleSet {
+            allowlist: vec![Entry {
+                rule: Rule::name("code.exe"),
+                comment: String::new(),
+            }],
+            ..RuleSet::default()
+        };
+        assert!(
+            app.import_rules(DEFAULT_PROFILE, &set, false).is_err(),
+            "the file didn't read, so it can't be saved"
+        );
+        assert!(!path.exists(), "and nothing was written over it");
+    }
+
+    #[test]
+    fn the_config_is_written_once_it_settles() {
+        let dir = std::env::temp_dir().join(format!("expurgate-settle-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let (mut app, _) = MockBackend::desk().app();
+        app.config_path = Some(path.clone());
+        app.save_config().expect("saves");
+
+        let ctx = egui::Context::default();
+        app.undo_secs = 5;
+        app.save_config_when_settled(&ctx);
+        let undo_secs = || Config::load(&path).expect("written").undo_secs;
+        assert_eq!(undo_secs(), 60, "not while it might still be changing");
+
+        std::thread::sleep(SETTLE);
+        app.save_config_when_settled(&ctx);
+        assert_eq!(undo_secs(), 5, "once it's been left alone");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn locked_rules_stay_out_of_the_users_file() {
+        let dir = std::env::temp_dir().join(format!("expurgate-policy-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let (mut app, _) = MockBackend::desk().app();
+        app.config_path = Some(path.clone());
+        let policy = RuleSet {
+            allowlist: vec![Entry {
+                rule: Rule::name("exam-client"),
+                comment: "needed for exams".to_owned(),
+            }],
+            ..RuleSet::default()
+        };
+        app.layers = Layers::new(&policy, &RuleSet::default());
+
+        let mut mine = Config::default();
+        mine.profiles
+            .active_mut()
+            .allowlist
+            .insert(Rule::name("code.exe"));
+        app.apply_config(mine.clone());
+        assert!(
+            app.profiles
+                .active()
+                .allowlist
+                .contains(&Rule::name("exam-client")),
+            "the policy's on top"
+        );
+
+        app.profiles
+            .active_mut()
+            .killlist
+            .insert(Rule::name("steam.exe"));
+        app.save_config().expect("saves");
+        let on_disk = Config::load(&path).expect("written");
+        mine.profiles
+            .active_mut()
+            .killlist
+            .insert(Rule::name("steam.exe"));
+        assert_eq!(on_disk, mine, "only what the user set");
+
+        app.reload_config(Ok(on_disk));
+        assert!(!app.config_changed(), "our own write coming back");
+        assert!(
+            app.profiles
+                .active()
+                .allowlist
+                .contains(&Rule::name("exam-client")),
+            "and still locked"
+        );
+
+        let exported = app
+            .export_rules(DEFAULT_PROFILE)
+            .expect("there's a default profile");
+        assert_eq!(
+            exported
+                .allowlist
+                .iter()
+                .map(|entry| &entry.rule)
+                .collect::<Vec<_>>(),
+            [&Rule::name("code.exe")],
+            "the policy isn't the user's to share"
+        );
+        std::fs::remove_dir_all(dir).ok();
     }
 }
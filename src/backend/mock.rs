@@ -0,0 +1,223 @@
+//! A fake backend that's just data, for tests and for poking at the filters without
+//! having to actually open 40 apps.
+
+use std::collections::BTreeMap;
+use std::io;
+#[cfg(test)]
+use std::sync::Arc;
+use std::sync::{Mutex, PoisonError};
+
+use super::{WindowBackend, WindowId};
+use crate::processes::ProcessInfo;
+
+/// A window as far as the filters care, roughly what Win32 tells us about an HWND.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockWindow {
+    pub id: WindowId,
+    pub pid: u32,
+    pub title: String,
+    pub class: String,
+    pub visible: bool,
+    pub cloaked: bool,
+    pub toolwindow: bool,
+    /// The owner window, popups and dialogs have one.
+    pub owner: Option<WindowId>,
+    /// The pid is a guess, like on wayland.
+    pub guessed: bool,
+}
+
+impl MockWindow {
+    /// A plain visible app window.
+    pub fn new(id: WindowId, pid: u32) -> Self {
+        Self {
+            id,
+            pid,
+            title: String::new(),
+            class: String::new(),
+            visible: true,
+            cloaked: false,
+            toolwindow: false,
+            owner: None,
+            guessed: false,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        title.clone_into(&mut self.title);
+        self
+    }
+
+    pub fn class(mut self, class: &str) -> Self {
+        class.clone_into(&mut self.class);
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+
+    pub fn cloaked(mut self) -> Self {
+        self.cloaked = true;
+        self
+    }
+
+    pub fn toolwindow(mut self) -> Self {
+        self.toolwindow = true;
+        self
+    }
+
+    pub fn owned_by(mut self, owner: WindowId) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn guessed(mut self) -> Self {
+        self.guessed = true;
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct MockBackend {
+    processes: BTreeMap<u32, ProcessInfo>,
+    windows: Vec<MockWindow>,
+    focused: Option<WindowId>,
+    closed: Mutex<Vec<WindowId>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(self, pid: u32, name: &str) -> Self {
+        self.process_info(pid, ProcessInfo::new(name))
+    }
+
+    /// A process with an exe path or arguments, for the rules that look at those.
+    pub fn process_info(mut self, pid: u32, info: ProcessInfo) -> Self {
+        self.processes.insert(pid, info);
+        self
+    }
+
+    pub fn window(mut self, window: MockWindow) -> Self {
+        self.windows.push(window);
+        self
+    }
+
+    pub fn focused(mut self, window: WindowId) -> Self {
+        self.focused = Some(window);
+        self
+    }
+
+    /// Processes by pid, what sysinfo would give us.
+    pub fn processes(&self) -> &BTreeMap<u32, ProcessInfo> {
+        &self.processes
+    }
+
+    /// Every window we were asked to close, in order.
+    pub fn closed(&self) -> Vec<WindowId> {
+        self.closed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn get(&self, window: WindowId) -> Option<&MockWindow> {
+        self.windows.iter().find(|w| w.id == window)
+    }
+}
+
+#[cfg(test)]
+impl MockBackend {
+    /// A desk you'd recognize: an editor, notepad, a shell helper, us and a tool window.
+    pub fn desk() -> Self {
+        Self::new()
+            .process(100, "code.exe")
+            .window(MockWindow::new(1, 100).title("main.rs - Visual Studio Code"))
+            .process(200, "notepad.exe")
+            .window(MockWindow::new(2, 200).title("Untitled - Notepad"))
+            .process(300, "SearchHost.exe")
+            .window(MockWindow::new(3, 300))
+            .process(400, "expurgate.exe")
+            .window(MockWindow::new(4, 400).title("expurgate"))
+            .process(500, "Figma.exe")
+            .window(MockWindow::new(5, 500).toolwindow())
+    }
+
+    /// The app on this, with its processes already looked at.
+    pub fn app(self) -> (crate::Expurgate, Arc<Self>) {
+        let mock = Arc::new(self);
+        let mut app = crate::Expurgate::with_backend(mock.clone());
+        app.update_processlists(mock.processes());
+        (app, mock)
+    }
+}
+
+impl WindowBackend for MockBackend {
+    fn top_level_windows(&self) -> Vec<WindowId> {
+        self.windows.iter().map(|w| w.id).collect()
+    }
+
+    fn owner_pid(&self, window: WindowId) -> Option<u32> {
+        self.get(window).map(|w| w.pid)
+    }
+
+    fn owner_is_guessed(&self, window: WindowId) -> bool {
+        self.get(window).is_some_and(|w| w.guessed)
+    }
+
+    fn title(&self, window: WindowId) -> String {
+        self.get(window)
+            .map(|w| w.title.clone())
+            .unwrap_or_default()
+    }
+
+    fn class(&self, window: WindowId) -> String {
+        self.get(window)
+            .map(|w| w.class.clone())
+            .unwrap_or_default()
+    }
+
+    fn is_visible(&self, window: WindowId) -> bool {
+        self.get(window).is_some_and(|w| w.visible)
+    }
+
+    // the same dance as the win32 one: go up to the root owner, take a visible popup of
+    // it if the root itself is hidden, then check that one
+    fn is_in_taskbar(&self, window: WindowId) -> bool {
+        let Some(mut root) = self.get(window) else {
+            return false;
+        };
+        while let Some(owner) = root.owner.and_then(|owner| self.get(owner)) {
+            root = owner;
+        }
+
+        let shown = if root.visible {
+            root
+        } else {
+            self.windows
+                .iter()
+                .find(|w| w.owner == Some(root.id) && w.visible)
+                .unwrap_or(root)
+        };
+
+        shown.visible && !shown.cloaked && !shown.toolwindow
+    }
+
+    fn focused_window(&self) -> Option<WindowId> {
+        self.focused
+    }
+
+    fn close(&self, window: WindowId) -> io::Result<()> {
+        if self.get(window).is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such window"));
+        }
+        self.closed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(window);
+        Ok(())
+    }
+}
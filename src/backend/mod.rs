@@ -0,0 +1,202 @@
+//! Everything that lists or closes windows goes through [`WindowBackend`],
+//! so the allowlist/killlist logic doesn't care what's drawing them.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod mock;
+
+#[cfg(windows)]
+mod win32;
+
+#[cfg(windows)]
+pub use win32::Win32Backend;
+
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[cfg(target_os = "linux")]
+pub use x11::X11Backend;
+
+#[cfg(target_os = "linux")]
+mod wayland;
+
+#[cfg(target_os = "linux")]
+pub use wayland::WaylandBackend;
+
+/// Opaque handle of a top-level window, whatever that means on the platform (an HWND on Windows).
+pub type WindowId = u64;
+
+/// Something that happened to a top-level window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowEvent {
+    Opened(WindowId),
+    Closed(WindowId),
+    /// Still there, but not shown anymore.
+    Hidden(WindowId),
+    /// `None` when nothing has focus, the desktop say.
+    Focused(Option<WindowId>),
+}
+
+/// Where a backend sends [`WindowEvent`]s, returns false once nobody's listening.
+pub type OnWindowEvent = Box<dyn Fn(WindowEvent) -> bool + Send>;
+
+/// What changed between two looks at the top-level windows, closed ones first.
+pub fn window_changes(before: &BTreeSet<WindowId>, after: &BTreeSet<WindowId>) -> Vec<WindowEvent> {
+    let closed = before
+        .difference(after)
+        .map(|window| WindowEvent::Closed(*window));
+    let opened = after
+        .difference(before)
+        .map(|window| WindowEvent::Opened(*window));
+    closed.chain(opened).collect()
+}
+
+pub trait WindowBackend: Send + Sync {
+    /// All top-level windows, hidden ones included.
+    fn top_level_windows(&self) -> Vec<WindowId>;
+
+    /// The process that owns the window, if the platform tells us.
+    fn owner_pid(&self, window: WindowId) -> Option<u32>;
+
+    /// Whether [`owner_pid`](Self::owner_pid) is only a guess, which might be some other
+    /// process. Those never get signals, only their windows closed.
+    fn owner_is_guessed(&self, _window: WindowId) -> bool {
+        false
+    }
+
+    fn title(&self, window: WindowId) -> String;
+
+    /// The window class, `WM_CLASS` on X11 and the `app_id` on Wayland.
+    fn class(&self, window: WindowId) -> String;
+
+    /// Whether the window is shown at all. Minimized still counts, like `WS_VISIBLE`.
+    fn is_visible(&self, window: WindowId) -> bool;
+
+    /// Whether the window (pseudo) shows up in the taskbar, i.e. it's something the user
+    /// would consider an open app and not some helper or tool window.
+    fn is_in_taskbar(&self, window: WindowId) -> bool;
+
+    /// The window with keyboard focus, if the platform tells us.
+    fn focused_window(&self) -> Option<WindowId>;
+
+    /// Asks the window to close, same as pressing the X. Never kills anything.
+    fn close(&self, window: WindowId) -> io::Result<()>;
+
+    /// How long since the last keyboard or mouse input, if the platform tells us.
+    fn idle_time(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Sends window events to `on_event` from a thread of its own from now on. False if
+    /// the platform doesn't do events or they couldn't be set up, the windows get polled then.
+    fn subscribe(&self, _on_event: OnWindowEvent) -> bool {
+        false
+    }
+
+    fn windows_of(&self, pid: u32) -> Vec<WindowId> {
+        self.top_level_windows()
+            .into_iter()
+            .filter(|window| self.owner_pid(*window) == Some(pid))
+            .collect()
+    }
+
+    fn first_window_of(&self, pid: u32) -> Option<WindowId> {
+        self.top_level_windows()
+            .into_iter()
+            .find(|window| self.owner_pid(*window) == Some(pid))
+    }
+}
+
+/// Used where we don't have a backend (yet), sees no windows and closes nothing.
+pub struct UnsupportedBackend;
+
+impl WindowBackend for UnsupportedBackend {
+    fn top_level_windows(&self) -> Vec<WindowId> {
+        Vec::new()
+    }
+
+    fn owner_pid(&self, _window: WindowId) -> Option<u32> {
+        None
+    }
+
+    fn title(&self, _window: WindowId) -> String {
+        String::new()
+    }
+
+    fn class(&self, _window: WindowId) -> String {
+        String::new()
+    }
+
+    fn is_visible(&self, _window: WindowId) -> bool {
+        false
+    }
+
+    fn is_in_taskbar(&self, _window: WindowId) -> bool {
+        false
+    }
+
+    fn focused_window(&self) -> Option<WindowId> {
+        None
+    }
+
+    fn close(&self, _window: WindowId) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "no window backend for this platform",
+        ))
+    }
+}
+
+/// The backend for the platform we're running on.
+pub fn native() -> Arc<dyn WindowBackend> {
+    #[cfg(windows)]
+    {
+        Arc::new(Win32Backend)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // xwayland would only show us the x11 apps, so wayland goes first
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match WaylandBackend::connect() {
+                Ok(backend) => return Arc::new(backend),
+                Err(e) => log::warn!("No foreign toplevel manager, trying X11: {e}"),
+            }
+        }
+        match X11Backend::connect(None) {
+            Ok(backend) => Arc::new(backend),
+            Err(e) => {
+                log::warn!("Couldn't connect to X11, no windows for us: {e}");
+                Arc::new(UnsupportedBackend)
+            }
+        }
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        Arc::new(UnsupportedBackend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{WindowEvent, window_changes};
+
+    #[test]
+    fn changes_between_two_looks() {
+        let before = BTreeSet::from([1, 2, 3]);
+        let after = BTreeSet::from([2, 3, 4]);
+        assert_eq!(
+            window_changes(&before, &after),
+            [WindowEvent::Closed(1), WindowEvent::Opened(4)],
+            "one went, one came"
+        );
+        assert!(
+            window_changes(&after, &after).is_empty(),
+            "nothing happened"
+        );
+    }
+}
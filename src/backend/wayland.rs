@@ -0,0 +1,350 @@
+//! Wayland through `zwlr_foreign_toplevel_manager_v1`, which sway, Hyprland and most
+//! other wlroots-ish compositors hand out to taskbars.
+//!
+//! The protocol doesn't tell us pids, so we guess them from the `app_id`. Guesses can be
+//! wrong, so those only ever get their toplevels closed, never a signal.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+use wayland_client::globals::{GlobalListContents, registry_queue_init};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::{Connection, Dispatch, Proxy as _, QueueHandle, event_created_child};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::{
+    self, ZwlrForeignToplevelHandleV1,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::{
+    self, ZwlrForeignToplevelManagerV1,
+};
+
+use super::{WindowBackend, WindowId};
+
+// zwlr_foreign_toplevel_handle_v1.state.activated
+const ACTIVATED: u32 = 2;
+
+// what the compositor told us about a toplevel so far
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Toplevel {
+    title: String,
+    app_id: String,
+    pid: Option<u32>,
+    has_parent: bool,
+    activated: bool,
+}
+
+impl Toplevel {
+    // `pid` is the guess for the new app_id, only looked at with one
+    fn apply(&mut self, event: zwlr_foreign_toplevel_handle_v1::Event, pid: Option<u32>) {
+        use zwlr_foreign_toplevel_handle_v1::Event;
+
+        match event {
+            Event::Title { title } => self.title = title,
+            Event::AppId { app_id } => {
+                self.app_id = app_id;
+                self.pid = pid;
+            }
+            Event::Parent { parent } => self.has_parent = parent.is_some(),
+            // an array of native endian u32 states
+            Event::State { state } => {
+                self.activated = state
+                    .chunks_exact(4)
+                    .filter_map(|chunk| chunk.try_into().ok().map(u32::from_ne_bytes))
+                    .any(|state| state == ACTIVATED);
+            }
+            _ => {}
+        }
+    }
+}
+
+type Toplevels = Arc<Mutex<BTreeMap<WindowId, (ZwlrForeignToplevelHandleV1, Toplevel)>>>;
+
+// lives on the dispatch thread, the backend only ever looks at `toplevels`
+struct State {
+    toplevels: Toplevels,
+    sys: System,
+}
+
+pub struct WaylandBackend {
+    conn: Connection,
+    toplevels: Toplevels,
+}
+
+impl WaylandBackend {
+    /// Connects to `$WAYLAND_DISPLAY` and starts listening for toplevels.
+    ///
+    /// Fails if the compositor doesn't have the foreign toplevel manager (GNOME doesn't).
+    pub fn connect() -> io::Result<Self> {
+        let conn = Connection::connect_to_env().map_err(io::Error::other)?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(io::Error::other)?;
+        globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(&queue.handle(), 1..=3, ())
+            .map_err(io::Error::other)?;
+
+        let toplevels = Toplevels::default();
+        let mut state = State {
+            toplevels: toplevels.clone(),
+            sys: System::new(),
+        };
+
+        // so the first listing isn't empty
+        queue.roundtrip(&mut state).map_err(io::Error::other)?;
+
+        thread::spawn(move || {
+            while queue.blocking_dispatch(&mut state).is_ok() {}
+            log::warn!("Lost the wayland connection, toplevels won't update anymore");
+        });
+
+        Ok(Self { conn, toplevels })
+    }
+
+    fn with_toplevel<T>(&self, window: WindowId, f: impl FnOnce(&Toplevel) -> T) -> Option<T> {
+        let toplevels = self
+            .toplevels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        toplevels.get(&window).map(|(_, toplevel)| f(toplevel))
+    }
+}
+
+// best effort: a process called like the app_id, or like its last part
+// ("org.gnome.Nautilus" -> "nautilus"), out of (pid, name, start time)s
+fn guess_pid<'a>(
+    app_id: &str,
+    processes: impl IntoIterator<Item = (u32, &'a str, u64)>,
+) -> Option<u32> {
+    let app_id = app_id.to_ascii_lowercase();
+    let short = app_id.rsplit('.').next().unwrap_or(&app_id);
+    processes
+        .into_iter()
+        .filter(|(_, name, _)| {
+            let name = name.to_ascii_lowercase();
+            name == app_id || name == short
+        })
+        // the oldest one is most likely the main process and not some child
+        .min_by_key(|(pid, _, start_time)| (*start_time, *pid))
+        .map(|(pid, _, _)| pid)
+}
+
+impl State {
+    fn guess_pid(&mut self, app_id: &str) -> Option<u32> {
+        self.sys.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing(),
+        );
+        // names that aren't utf-8 don't look like any app_id anyway
+        let processes = self.sys.processes().iter().map(|(pid, process)| {
+            let name = process.name().to_str().unwrap_or_default();
+            (pid.as_u32(), name, process.start_time())
+        });
+        guess_pid(app_id, processes)
+    }
+}
+
+impl WindowBackend for WaylandBackend {
+    fn top_level_windows(&self) -> Vec<WindowId> {
+        let toplevels = self
+            .toplevels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        toplevels.keys().copied().collect()
+    }
+
+    fn owner_pid(&self, window: WindowId) -> Option<u32> {
+        self.with_toplevel(window, |toplevel| toplevel.pid)
+            .flatten()
+    }
+
+    // every one of them, there's nothing better to go on
+    fn owner_is_guessed(&self, _window: WindowId) -> bool {
+        true
+    }
+
+    fn title(&self, window: WindowId) -> String {
+        self.with_toplevel(window, |toplevel| toplevel.title.clone())
+            .unwrap_or_default()
+    }
+
+    fn class(&self, window: WindowId) -> String {
+        self.with_toplevel(window, |toplevel| toplevel.app_id.clone())
+            .unwrap_or_default()
+    }
+
+    fn is_visible(&self, window: WindowId) -> bool {
+        // minimized still counts, and the compositor doesn't list anything hidden
+        let toplevels = self
+            .toplevels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        toplevels.contains_key(&window)
+    }
+
+    fn is_in_taskbar(&self, window: WindowId) -> bool {
+        // the compositor only lists taskbar material anyway, we just skip dialogs
+        self.with_toplevel(window, |toplevel| !toplevel.has_parent)
+            .unwrap_or(false)
+    }
+
+    fn focused_window(&self) -> Option<WindowId> {
+        let toplevels = self
+            .toplevels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        toplevels
+            .iter()
+            .find(|(_, (_, toplevel))| toplevel.activated)
+            .map(|(id, _)| *id)
+    }
+
+    fn close(&self, window: WindowId) -> io::Result<()> {
+        let toplevels = self
+            .toplevels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (handle, _) = toplevels
+            .get(&window)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "toplevel is already gone"))?;
+        handle.close();
+        drop(toplevels);
+        self.conn.flush().map_err(io::Error::other)
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            let id = WindowId::from(toplevel.id().protocol_id());
+            let mut toplevels = state
+                .toplevels
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            toplevels.insert(id, (toplevel, Toplevel::default()));
+        }
+    }
+
+    event_created_child!(Self, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::Event;
+
+        let id = WindowId::from(handle.id().protocol_id());
+
+        // looked up outside the lock, it walks /proc
+        let pid = match &event {
+            Event::AppId { app_id } => Some(state.guess_pid(app_id)),
+            _ => None,
+        };
+
+        let mut toplevels = state
+            .toplevels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if matches!(event, Event::Closed) {
+            if let Some((handle, _)) = toplevels.remove(&id) {
+                handle.destroy();
+            }
+            return;
+        }
+        if let Some((_, toplevel)) = toplevels.get_mut(&id) {
+            toplevel.apply(event, pid.flatten());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::Event;
+
+    use super::{ACTIVATED, Toplevel, guess_pid};
+
+    #[test]
+    fn pids_are_guessed_from_the_app_id() {
+        let processes = [
+            (300, "nautilus", 20),
+            (200, "nautilus", 10),
+            (100, "firefox", 5),
+        ];
+        assert_eq!(
+            guess_pid("org.gnome.Nautilus", processes),
+            Some(200),
+            "the last part, and the oldest of them"
+        );
+        assert_eq!(guess_pid("Firefox", processes), Some(100), "any case");
+        assert_eq!(guess_pid("foot", processes), None, "nothing called that");
+    }
+
+    #[test]
+    fn toplevels_keep_up_with_their_events() {
+        let mut toplevel = Toplevel::default();
+        toplevel.apply(
+            Event::Title {
+                title: "Downloads".to_owned(),
+            },
+            None,
+        );
+        toplevel.apply(
+            Event::AppId {
+                app_id: "org.gnome.Nautilus".to_owned(),
+            },
+            Some(200),
+        );
+        toplevel.apply(
+            Event::State {
+                state: [1, ACTIVATED]
+                    .iter()
+                    .flat_map(|s| s.to_ne_bytes())
+                    .collect(),
+            },
+            None,
+        );
+        assert_eq!(
+            toplevel,
+            Toplevel {
+                title: "Downloads".to_owned(),
+                app_id: "org.gnome.Nautilus".to_owned(),
+                pid: Some(200),
+                has_parent: false,
+                activated: true,
+            },
+            "everything it was told"
+        );
+
+        toplevel.apply(Event::State { state: Vec::new() }, Some(1));
+        toplevel.apply(Event::Parent { parent: None }, Some(1));
+        assert!(!toplevel.activated, "lost focus");
+        assert_eq!(toplevel.pid, Some(200), "only a new app_id changes the pid");
+    }
+}
@@ -0,0 +1,266 @@
+use std::cell::RefCell;
+use std::io;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::Graphics::Dwm::{DWMWA_CLOAKED, DwmGetWindowAttribute};
+use windows::Win32::System::SystemInformation::GetTickCount;
+use windows::Win32::UI::Accessibility::{HWINEVENTHOOK, SetWinEventHook, UnhookWinEvent};
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CHILDID_SELF, EVENT_OBJECT_DESTROY, EVENT_OBJECT_HIDE, EVENT_OBJECT_SHOW,
+    EVENT_SYSTEM_FOREGROUND, EnumWindows, GA_ROOT, GA_ROOTOWNER, GWL_EXSTYLE, GetAncestor,
+    GetClassNameW, GetForegroundWindow, GetLastActivePopup, GetMessageW, GetWindowLongW,
+    GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible, MSG,
+    OBJID_WINDOW, PostMessageW, PostQuitMessage, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+    WM_CLOSE, WS_EX_TOOLWINDOW,
+};
+use windows::core::BOOL;
+
+use super::{OnWindowEvent, WindowBackend, WindowEvent, WindowId};
+
+pub struct Win32Backend;
+
+fn hwnd(window: WindowId) -> HWND {
+    HWND(window as usize as *mut _)
+}
+
+fn window_id(hwnd: HWND) -> WindowId {
+    hwnd.0 as usize as WindowId
+}
+
+#[allow(unsafe_code)]
+pub fn is_pseudo_open_in_taskbar(mut hwnd: HWND) -> bool {
+    unsafe {
+        // Finding a visible popup
+        let root = GetAncestor(hwnd, GA_ROOTOWNER);
+        let mut last = root;
+        loop {
+            let popup = GetLastActivePopup(last);
+            if popup == last {
+                break;
+            }
+            if IsWindowVisible(popup).as_bool() {
+                last = popup;
+                break;
+            }
+            last = popup;
+        }
+
+        // if the root is invisible but the popup is visible, we use the popup
+        if !IsWindowVisible(last).as_bool() {
+            let popup = GetLastActivePopup(root);
+            if IsWindowVisible(popup).as_bool() {
+                last = popup;
+            }
+        }
+
+        hwnd = last;
+
+        // Is cloaked?
+        let mut cloaked: u32 = 0;
+        if DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut _ as _,
+            size_of::<u32>() as u32,
+        )
+        .is_ok()
+            && cloaked != 0
+        {
+            return false;
+        }
+
+        let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+
+        // Is tool window
+        if ex_style & WS_EX_TOOLWINDOW.0 != 0 {
+            return false;
+        }
+
+        // Is visible?
+        IsWindowVisible(hwnd).as_bool()
+    }
+}
+
+thread_local! {
+    // win event hooks don't take any context, so the callback lives with the hook's thread
+    static ON_EVENT: RefCell<Option<OnWindowEvent>> = const { RefCell::new(None) };
+}
+
+#[allow(unsafe_code)]
+extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    idobject: i32,
+    idchild: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    // only whole windows, not the carets and scrollbars inside them
+    if idobject != OBJID_WINDOW.0 || idchild != CHILDID_SELF as i32 {
+        return;
+    }
+    let event = match event {
+        EVENT_SYSTEM_FOREGROUND => {
+            WindowEvent::Focused((!hwnd.is_invalid()).then(|| window_id(hwnd)))
+        }
+        // child windows show and get destroyed too, only top-level ones open
+        EVENT_OBJECT_SHOW if unsafe { GetAncestor(hwnd, GA_ROOT) } == hwnd => {
+            WindowEvent::Opened(window_id(hwnd))
+        }
+        EVENT_OBJECT_HIDE if unsafe { GetAncestor(hwnd, GA_ROOT) } == hwnd => {
+            WindowEvent::Hidden(window_id(hwnd))
+        }
+        EVENT_OBJECT_DESTROY => WindowEvent::Closed(window_id(hwnd)),
+        _ => return,
+    };
+    let listening = ON_EVENT.with_borrow(|on_event| on_event.as_ref().is_some_and(|f| f(event)));
+    if !listening {
+        unsafe { PostQuitMessage(0) };
+    }
+}
+
+// out of context hooks get called from this thread's message loop. `installed` hears
+// whether the hooks are in before it starts
+#[allow(unsafe_code)]
+fn watch(on_event: OnWindowEvent, installed: &Sender<bool>) {
+    ON_EVENT.set(Some(on_event));
+    let flags = WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS;
+    let hooks = unsafe {
+        [
+            SetWinEventHook(
+                EVENT_SYSTEM_FOREGROUND,
+                EVENT_SYSTEM_FOREGROUND,
+                None,
+                Some(win_event_proc),
+                0,
+                0,
+                flags,
+            ),
+            // show is in between the two
+            SetWinEventHook(
+                EVENT_OBJECT_DESTROY,
+                EVENT_OBJECT_HIDE,
+                None,
+                Some(win_event_proc),
+                0,
+                0,
+                flags,
+            ),
+        ]
+    };
+    let unhook = |hooks: [HWINEVENTHOOK; 2]| {
+        for hook in hooks.into_iter().filter(|hook| !hook.is_invalid()) {
+            unsafe {
+                let _ = UnhookWinEvent(hook);
+            }
+        }
+    };
+    // half of them would miss windows without anyone noticing, polling it is then
+    if hooks.iter().any(HWINEVENTHOOK::is_invalid) {
+        log::warn!("Failed to hook window events, looking for them every so often instead");
+        unhook(hooks);
+        installed.send(false).ok();
+        return;
+    }
+    installed.send(true).ok();
+
+    let mut msg = MSG::default();
+    unsafe { while GetMessageW(&mut msg, None, 0, 0).into() {} }
+    unhook(hooks);
+}
+
+impl WindowBackend for Win32Backend {
+    #[allow(unsafe_code)]
+    fn top_level_windows(&self) -> Vec<WindowId> {
+        extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+            let windows = unsafe { &mut *(lparam.0 as *mut Vec<WindowId>) };
+            windows.push(window_id(hwnd));
+            BOOL(1) // continuing enumeration
+        }
+
+        let mut windows: Vec<WindowId> = Vec::new();
+        unsafe {
+            let _ = EnumWindows(
+                Some(enum_windows_proc),
+                LPARAM(&mut windows as *mut _ as isize),
+            );
+        }
+        windows
+    }
+
+    #[allow(unsafe_code)]
+    fn owner_pid(&self, window: WindowId) -> Option<u32> {
+        let mut pid = 0;
+        unsafe {
+            GetWindowThreadProcessId(hwnd(window), Some(&mut pid));
+        }
+        (pid != 0).then_some(pid)
+    }
+
+    #[allow(unsafe_code)]
+    fn title(&self, window: WindowId) -> String {
+        unsafe {
+            let len = GetWindowTextLengthW(hwnd(window));
+            if len <= 0 {
+                return String::new();
+            }
+            let mut buf = vec![0u16; len as usize + 1];
+            let copied = GetWindowTextW(hwnd(window), &mut buf);
+            String::from_utf16_lossy(&buf[..copied.max(0) as usize])
+        }
+    }
+
+    #[allow(unsafe_code)]
+    fn class(&self, window: WindowId) -> String {
+        // class names are capped at 256 characters
+        let mut buf = [0u16; 257];
+        let copied = unsafe { GetClassNameW(hwnd(window), &mut buf) };
+        String::from_utf16_lossy(&buf[..copied.max(0) as usize])
+    }
+
+    #[allow(unsafe_code)]
+    fn is_visible(&self, window: WindowId) -> bool {
+        unsafe { IsWindowVisible(hwnd(window)).as_bool() }
+    }
+
+    fn is_in_taskbar(&self, window: WindowId) -> bool {
+        is_pseudo_open_in_taskbar(hwnd(window))
+    }
+
+    #[allow(unsafe_code)]
+    fn focused_window(&self) -> Option<WindowId> {
+        let hwnd = unsafe { GetForegroundWindow() };
+        (!hwnd.is_invalid()).then(|| window_id(hwnd))
+    }
+
+    #[allow(unsafe_code)]
+    fn close(&self, window: WindowId) -> io::Result<()> {
+        unsafe { PostMessageW(Some(hwnd(window)), WM_CLOSE, WPARAM(0), LPARAM(0))? };
+        Ok(())
+    }
+
+    #[allow(unsafe_code)]
+    fn idle_time(&self) -> Option<Duration> {
+        let mut info = LASTINPUTINFO {
+            cbSize: size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        if !unsafe { GetLastInputInfo(&mut info) }.as_bool() {
+            return None;
+        }
+        // both wrap around every 49 days, the difference doesn't care
+        let now = unsafe { GetTickCount() };
+        Some(Duration::from_millis(now.wrapping_sub(info.dwTime).into()))
+    }
+
+    fn subscribe(&self, on_event: OnWindowEvent) -> bool {
+        let (installed_tx, installed) = mpsc::channel();
+        thread::spawn(move || watch(on_event, &installed_tx));
+        installed.recv().unwrap_or(false)
+    }
+}
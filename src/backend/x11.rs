@@ -0,0 +1,560 @@
+//! X11 through the EWMH hints most window managers keep up to date.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use x11rb::connection::Connection as _;
+use x11rb::protocol::Event;
+use x11rb::protocol::screensaver::ConnectionExt as _;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt as _, EventMask,
+    MapState, Window,
+};
+use x11rb::rust_connection::RustConnection;
+
+use super::{OnWindowEvent, WindowBackend, WindowEvent, WindowId, window_changes};
+
+x11rb::atom_manager! {
+    pub Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_CLOSE_WINDOW,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_STATE,
+        _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        WM_DELETE_WINDOW,
+        WM_PROTOCOLS,
+        WM_STATE,
+        UTF8_STRING,
+    }
+}
+
+pub struct X11Backend {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    // so watching for events can get a connection of its own
+    display: Option<String>,
+}
+
+impl X11Backend {
+    /// Connects to `display`, or `$DISPLAY` if that's `None`.
+    pub fn connect(display: Option<&str>) -> io::Result<Self> {
+        let (conn, screen) = x11rb::connect(display).map_err(io::Error::other)?;
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn)
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            conn,
+            root,
+            atoms,
+            display: display.map(str::to_owned),
+        })
+    }
+
+    fn property(&self, window: Window, property: Atom, type_: impl Into<Atom>) -> Option<Vec<u8>> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, type_, 0, u32::MAX)
+            .ok()?
+            .reply()
+            .ok()?;
+        (reply.format != 0).then_some(reply.value)
+    }
+
+    fn property32(&self, window: Window, property: Atom, type_: impl Into<Atom>) -> Vec<u32> {
+        self.conn
+            .get_property(false, window, property, type_, 0, u32::MAX)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().map(Iterator::collect))
+            .unwrap_or_default()
+    }
+
+    fn send_client_message(
+        &self,
+        destination: Window,
+        event: ClientMessageEvent,
+        mask: EventMask,
+    ) -> io::Result<()> {
+        self.conn
+            .send_event(false, destination, mask, event)
+            .map_err(io::Error::other)?;
+        self.conn.flush().map_err(io::Error::other)
+    }
+
+    // the window manager keeps the client list and the active window on the root window,
+    // substructure events catch windows going away before it gets around to it
+    fn select_root_events(&self) -> io::Result<()> {
+        self.conn
+            .change_window_attributes(
+                self.root,
+                &ChangeWindowAttributesAux::new()
+                    .event_mask(EventMask::PROPERTY_CHANGE | EventMask::SUBSTRUCTURE_NOTIFY),
+            )
+            .map_err(io::Error::other)?
+            .check()
+            .map_err(io::Error::other)
+    }
+
+    // keeps its own idea of what's open, starting with `windows`, so it only reports what
+    // changed
+    fn watch(&self, mut windows: BTreeSet<WindowId>, on_event: &OnWindowEvent) {
+        let mut focused = self.focused_window();
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Stopped watching windows: {e}");
+                    return;
+                }
+            };
+            let events = match event {
+                Event::PropertyNotify(event) if event.atom == self.atoms._NET_ACTIVE_WINDOW => {
+                    let now = self.focused_window();
+                    if now == focused {
+                        continue;
+                    }
+                    focused = now;
+                    vec![WindowEvent::Focused(now)]
+                }
+                Event::PropertyNotify(event) if event.atom == self.atoms._NET_CLIENT_LIST => {
+                    self.rescan(&mut windows)
+                }
+                Event::DestroyNotify(_) | Event::MapNotify(_) => self.rescan(&mut windows),
+                _ => continue,
+            };
+            for event in events {
+                if !on_event(event) {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn rescan(&self, windows: &mut BTreeSet<WindowId>) -> Vec<WindowEvent> {
+        let now = self.top_level_windows().into_iter().collect();
+        let changes = window_changes(windows, &now);
+        *windows = now;
+        changes
+    }
+}
+
+impl WindowBackend for X11Backend {
+    fn top_level_windows(&self) -> Vec<WindowId> {
+        self.property32(self.root, self.atoms._NET_CLIENT_LIST, AtomEnum::WINDOW)
+            .into_iter()
+            .map(WindowId::from)
+            .collect()
+    }
+
+    fn owner_pid(&self, window: WindowId) -> Option<u32> {
+        self.property32(window as Window, self.atoms._NET_WM_PID, AtomEnum::CARDINAL)
+            .first()
+            .copied()
+    }
+
+    fn title(&self, window: WindowId) -> String {
+        let window = window as Window;
+        self.property(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)
+            .or_else(|| self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING))
+            .map(|title| String::from_utf8_lossy(&title).into_owned())
+            .unwrap_or_default()
+    }
+
+    fn class(&self, window: WindowId) -> String {
+        // "instance\0Class\0", the second one is the one people know
+        let Some(class) = self.property(
+            window as Window,
+            AtomEnum::WM_CLASS.into(),
+            AtomEnum::STRING,
+        ) else {
+            return String::new();
+        };
+        let mut parts = class
+            .split(|byte| *byte == 0)
+            .filter(|part| !part.is_empty());
+        let instance = parts.next().unwrap_or_default();
+        String::from_utf8_lossy(parts.next().unwrap_or(instance)).into_owned()
+    }
+
+    fn is_visible(&self, window: WindowId) -> bool {
+        let window = window as Window;
+
+        // ICCCM WM_STATE: 1 is normal, 3 is iconic (minimized), 0 is withdrawn
+        if let Some(state) = self
+            .property32(window, self.atoms.WM_STATE, self.atoms.WM_STATE)
+            .first()
+        {
+            return *state == 1 || *state == 3;
+        }
+
+        self.conn
+            .get_window_attributes(window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|attributes| attributes.map_state == MapState::VIEWABLE)
+    }
+
+    fn is_in_taskbar(&self, window: WindowId) -> bool {
+        let window = window as Window;
+
+        // same idea as WS_EX_TOOLWINDOW on windows
+        let state = self.property32(window, self.atoms._NET_WM_STATE, AtomEnum::ATOM);
+        if state.contains(&self.atoms._NET_WM_STATE_SKIP_TASKBAR) {
+            return false;
+        }
+
+        // panels and the desktop itself are in the client list too
+        let types = self.property32(window, self.atoms._NET_WM_WINDOW_TYPE, AtomEnum::ATOM);
+        !types.iter().any(|type_| {
+            *type_ == self.atoms._NET_WM_WINDOW_TYPE_DESKTOP
+                || *type_ == self.atoms._NET_WM_WINDOW_TYPE_DOCK
+        })
+    }
+
+    fn focused_window(&self) -> Option<WindowId> {
+        self.property32(self.root, self.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW)
+            .first()
+            .filter(|window| **window != x11rb::NONE)
+            .map(|window| WindowId::from(*window))
+    }
+
+    fn close(&self, window: WindowId) -> io::Result<()> {
+        let window = window as Window;
+
+        let protocols = self.property32(window, self.atoms.WM_PROTOCOLS, AtomEnum::ATOM);
+        if protocols.contains(&self.atoms.WM_DELETE_WINDOW) {
+            // what the X button does
+            let event = ClientMessageEvent::new(
+                32,
+                window,
+                self.atoms.WM_PROTOCOLS,
+                [self.atoms.WM_DELETE_WINDOW, x11rb::CURRENT_TIME, 0, 0, 0],
+            );
+            return self.send_client_message(window, event, EventMask::NO_EVENT);
+        }
+
+        // the window doesn't speak WM_DELETE_WINDOW, so we ask the window manager instead
+        // (2 is "source indication: pager", i.e. the user asked for it)
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms._NET_CLOSE_WINDOW,
+            [x11rb::CURRENT_TIME, 2, 0, 0, 0],
+        );
+        self.send_client_message(
+            self.root,
+            event,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        )
+    }
+
+    // through the MIT-SCREEN-SAVER extension, most servers have it
+    fn idle_time(&self) -> Option<Duration> {
+        let info = self
+            .conn
+            .screensaver_query_info(self.root)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(Duration::from_millis(info.ms_since_user_input.into()))
+    }
+
+    // on a connection of its own, waiting for events would hold up everything else
+    fn subscribe(&self, on_event: OnWindowEvent) -> bool {
+        let watcher = Self::connect(self.display.as_deref()).and_then(|watcher| {
+            watcher.select_root_events()?;
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => {
+                // before returning, whatever opens after that is news
+                let windows = watcher.top_level_windows().into_iter().collect();
+                thread::spawn(move || watcher.watch(windows, &on_event));
+                true
+            }
+            Err(e) => {
+                log::warn!("Failed to watch for window changes, polling instead: {e}");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::{Child, Command};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use x11rb::connection::Connection as _;
+    use x11rb::protocol::Event;
+    use x11rb::protocol::xproto::{
+        AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux, EventMask,
+        PropMode, Window, WindowClass,
+    };
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+
+    use super::{Atoms, X11Backend};
+    use crate::backend::{WindowBackend as _, WindowEvent, WindowId};
+
+    // spawning our own Xvfb so the tests don't touch whatever display you're on
+    struct Xvfb {
+        server: Child,
+        display: String,
+    }
+
+    impl Xvfb {
+        fn start(number: u32) -> Self {
+            let display = format!(":{number}");
+            let server = Command::new("Xvfb")
+                .args([
+                    display.as_str(),
+                    "-screen",
+                    "0",
+                    "640x480x24",
+                    "-nolisten",
+                    "tcp",
+                ])
+                .spawn()
+                .expect("Failed to spawn Xvfb");
+
+            let socket = format!("/tmp/.X11-unix/X{number}");
+            let started = Instant::now();
+            while !Path::new(&socket).exists() {
+                assert!(
+                    started.elapsed() < Duration::from_secs(10),
+                    "Xvfb didn't come up"
+                );
+                thread::sleep(Duration::from_millis(50));
+            }
+
+            Self { server, display }
+        }
+
+        fn connect(&self) -> (RustConnection, Window, Atoms) {
+            let (conn, screen) =
+                x11rb::connect(Some(&self.display)).expect("Failed to connect to Xvfb");
+            let root = conn.setup().roots[screen].root;
+            let atoms = Atoms::new(&conn)
+                .expect("Failed to intern atoms")
+                .reply()
+                .expect("Failed to intern atoms");
+            (conn, root, atoms)
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            self.server.kill().ok();
+            self.server.wait().ok();
+        }
+    }
+
+    // a dummy client window, with a pid and a name like a real app would have
+    fn dummy_window(conn: &RustConnection, root: Window, atoms: &Atoms, pid: u32) -> Window {
+        let window = conn.generate_id().expect("Failed to generate id");
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            100,
+            100,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .expect("Failed to create window");
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_WM_PID,
+            AtomEnum::CARDINAL,
+            &[pid],
+        )
+        .expect("Failed to set pid");
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_WM_NAME,
+            atoms.UTF8_STRING,
+            format!("dummy {pid}").as_bytes(),
+        )
+        .expect("Failed to set name");
+        conn.map_window(window).expect("Failed to map window");
+        window
+    }
+
+    // there's no window manager on Xvfb, so we keep the client list ourselves
+    fn set_client_list(conn: &RustConnection, root: Window, atoms: &Atoms, windows: &[Window]) {
+        conn.change_property32(
+            PropMode::REPLACE,
+            root,
+            atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            windows,
+        )
+        .expect("Failed to set client list");
+        conn.flush().expect("Failed to flush");
+    }
+
+    fn wait_for_client_message(conn: &RustConnection) -> Option<(Window, u32, [u32; 5])> {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) {
+            if let Some(Event::ClientMessage(event)) =
+                conn.poll_for_event().expect("Failed to poll for events")
+            {
+                return Some((event.window, event.type_, event.data.as_data32()));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb on PATH"]
+    fn lists_and_filters_ewmh_clients() {
+        let xvfb = Xvfb::start(87);
+        let (client, root, atoms) = xvfb.connect();
+
+        let app = dummy_window(&client, root, &atoms, 4242);
+        let tool = dummy_window(&client, root, &atoms, 4343);
+        client
+            .change_property32(
+                PropMode::REPLACE,
+                tool,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                &[atoms._NET_WM_STATE_SKIP_TASKBAR],
+            )
+            .expect("Failed to set state");
+        set_client_list(&client, root, &atoms, &[app, tool]);
+
+        let backend = X11Backend::connect(Some(&xvfb.display)).expect("Failed to connect");
+        assert_eq!(
+            backend.top_level_windows(),
+            vec![WindowId::from(app), WindowId::from(tool)],
+            "client list should be read from the root window"
+        );
+        assert_eq!(backend.owner_pid(app.into()), Some(4242), "pid of the app");
+        assert_eq!(backend.title(app.into()), "dummy 4242", "title of the app");
+        assert!(backend.is_visible(app.into()), "app is mapped");
+        assert!(backend.is_in_taskbar(app.into()), "app is in the taskbar");
+        assert!(
+            !backend.is_in_taskbar(tool.into()),
+            "skip taskbar windows are not"
+        );
+        assert_eq!(
+            backend.first_window_of(4343),
+            Some(WindowId::from(tool)),
+            "lookup by pid"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb on PATH"]
+    fn closes_with_wm_delete_window() {
+        let xvfb = Xvfb::start(88);
+        let (client, root, atoms) = xvfb.connect();
+
+        let app = dummy_window(&client, root, &atoms, 4242);
+        client
+            .change_property32(
+                PropMode::REPLACE,
+                app,
+                atoms.WM_PROTOCOLS,
+                AtomEnum::ATOM,
+                &[atoms.WM_DELETE_WINDOW],
+            )
+            .expect("Failed to set protocols");
+        set_client_list(&client, root, &atoms, &[app]);
+
+        let backend = X11Backend::connect(Some(&xvfb.display)).expect("Failed to connect");
+        backend.close(app.into()).expect("Failed to close");
+
+        let (window, type_, data) =
+            wait_for_client_message(&client).expect("client never got asked to close");
+        assert_eq!(window, app, "message goes to the app window");
+        assert_eq!(type_, atoms.WM_PROTOCOLS, "it's a WM_PROTOCOLS message");
+        assert_eq!(
+            data[0], atoms.WM_DELETE_WINDOW,
+            "asking to delete the window"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb on PATH"]
+    fn falls_back_to_net_close_window() {
+        let xvfb = Xvfb::start(89);
+        let (client, root, atoms) = xvfb.connect();
+
+        // playing window manager, so the root window messages come to us
+        let (wm, _, _) = xvfb.connect();
+        wm.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_REDIRECT),
+        )
+        .expect("Failed to select root events")
+        .check()
+        .expect("Something else is managing the root window");
+
+        let stubborn = dummy_window(&client, root, &atoms, 4242);
+        set_client_list(&client, root, &atoms, &[stubborn]);
+
+        let backend = X11Backend::connect(Some(&xvfb.display)).expect("Failed to connect");
+        backend.close(stubborn.into()).expect("Failed to close");
+
+        let (window, type_, _) =
+            wait_for_client_message(&wm).expect("window manager never got asked to close");
+        assert_eq!(window, stubborn, "message is about the stubborn window");
+        assert_eq!(
+            type_, atoms._NET_CLOSE_WINDOW,
+            "it's a _NET_CLOSE_WINDOW request"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb on PATH"]
+    fn client_list_changes_come_as_events() {
+        // the hotkey tests have 90
+        let xvfb = Xvfb::start(91);
+        let (client, root, atoms) = xvfb.connect();
+
+        let backend = X11Backend::connect(Some(&xvfb.display)).expect("Failed to connect");
+        let (tx, rx) = mpsc::channel();
+        assert!(
+            backend.subscribe(Box::new(move |event| tx.send(event).is_ok())),
+            "x11 does events"
+        );
+
+        let app = dummy_window(&client, root, &atoms, 4242);
+        set_client_list(&client, root, &atoms, &[app]);
+        // one at a time, two quick changes could be read as one
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).ok(),
+            Some(WindowEvent::Opened(app.into())),
+            "came"
+        );
+        set_client_list(&client, root, &atoms, &[]);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).ok(),
+            Some(WindowEvent::Closed(app.into())),
+            "and went"
+        );
+    }
+}
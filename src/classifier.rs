@@ -0,0 +1,198 @@
+//! Decides what counts as a real app, as opposed to some system process or helper.
+//!
+//! Rules are the same as the allowlist ones: a default set everyone gets plus whatever
+//! the user adds, each of which can be turned off.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::processes::TrackedProcess;
+use crate::rules::Rule;
+
+/// What the old hardcoded check used to look for in process names.
+pub const DEFAULT_RULES: &[&str] = &[
+    "name=*service*",
+    "name=*helper*",
+    "name=*overlay*",
+    "name=*tray*",
+    "name=*host*",
+    "name=*broker*",
+    "name=*container*",
+    "name=*runtime*",
+    "name=*svchost*",
+    "name=*dwm*",
+    // TODO: handle these two manually later
+    "name=*explorer*",
+    "name=*taskmgr*",
+];
+
+/// Why a process isn't considered a real app.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filtered {
+    /// Pid 0 (idle) or 4 (System) on Windows, never touching those.
+    SystemPid,
+    Rule(Rule),
+}
+
+impl fmt::Display for Filtered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SystemPid => f.write_str("system process"),
+            Self::Rule(rule) => write!(f, "{rule}"),
+        }
+    }
+}
+
+fn default_rules() -> Vec<Rule> {
+    DEFAULT_RULES
+        .iter()
+        .map(|source| source.parse().expect("default rules are valid"))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Classifier {
+    // not persisted so changes to the defaults reach everyone
+    #[serde(skip, default = "default_rules")]
+    defaults: Vec<Rule>,
+
+    /// Added by the user on top of the defaults.
+    pub extra: BTreeSet<Rule>,
+
+    /// Turned off rules, default or not, by what they say.
+    pub disabled: BTreeSet<String>,
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Self {
+            defaults: default_rules(),
+            extra: BTreeSet::new(),
+            disabled: BTreeSet::new(),
+        }
+    }
+}
+
+impl Classifier {
+    pub fn defaults(&self) -> &[Rule] {
+        &self.defaults
+    }
+
+    pub fn is_enabled(&self, rule: &Rule) -> bool {
+        !self.disabled.contains(rule.as_str())
+    }
+
+    pub fn set_enabled(&mut self, rule: &Rule, enabled: bool) {
+        if enabled {
+            self.disabled.remove(rule.as_str());
+        } else {
+            self.disabled.insert(rule.to_string());
+        }
+    }
+
+    /// Why the process should be left alone, or `None` if it looks like a real app.
+    pub fn filtered(&self, pid: u32, process: &TrackedProcess) -> Option<Filtered> {
+        if pid == 0 || pid == 4 {
+            return Some(Filtered::SystemPid);
+        }
+        self.defaults
+            .iter()
+            .chain(&self.extra)
+            .filter(|rule| self.is_enabled(rule))
+            .find(|rule| rule.matches(process))
+            .map(|rule| Filtered::Rule(rule.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Classifier, Filtered};
+    use crate::processes::{ProcessInfo, TrackedProcess};
+    use crate::rules::Rule;
+
+    fn named(name: &str) -> TrackedProcess {
+        TrackedProcess {
+            info: ProcessInfo::new(name),
+            windows: Vec::new(),
+        }
+    }
+
+    fn rule(source: &str) -> Rule {
+        source.parse().expect("valid rule")
+    }
+
+    #[test]
+    fn real_apps_pass() {
+        let classifier = Classifier::default();
+        for name in ["code.exe", "notepad.exe", "firefox"] {
+            assert_eq!(
+                classifier.filtered(100, &named(name)),
+                None,
+                "{name} is an app"
+            );
+        }
+    }
+
+    #[test]
+    fn helpers_and_system_processes_are_filtered() {
+        let classifier = Classifier::default();
+        for name in [
+            "SearchHost.exe",
+            "svchost.exe",
+            "RuntimeBroker.exe",
+            "steamwebhelper.exe",
+            "NVIDIA Overlay.exe",
+            "dwm.exe",
+            "explorer.exe",
+            "Taskmgr.exe",
+        ] {
+            assert!(
+                classifier.filtered(1234, &named(name)).is_some(),
+                "{name} should be filtered"
+            );
+        }
+        assert_eq!(
+            classifier.filtered(0, &named("code.exe")),
+            Some(Filtered::SystemPid),
+            "pid 0 is idle"
+        );
+        assert_eq!(
+            classifier.filtered(4, &named("code.exe")),
+            Some(Filtered::SystemPid),
+            "pid 4 is System"
+        );
+    }
+
+    #[test]
+    fn tells_which_rule_filtered() {
+        let classifier = Classifier::default();
+        assert_eq!(
+            classifier.filtered(1234, &named("GHost.exe")),
+            Some(Filtered::Rule(rule("name=*host*"))),
+            "ghost gets caught by host"
+        );
+    }
+
+    #[test]
+    fn rules_can_be_disabled_and_added() {
+        let mut classifier = Classifier::default();
+        classifier.set_enabled(&rule("name=*host*"), false);
+        assert_eq!(
+            classifier.filtered(1234, &named("GHost.exe")),
+            None,
+            "ghost is back"
+        );
+        assert!(
+            classifier.filtered(1234, &named("svchost.exe")).is_some(),
+            "svchost still has its own rule"
+        );
+
+        classifier.extra.insert(rule("name=ghost*"));
+        assert_eq!(
+            classifier.filtered(1234, &named("GHost.exe")),
+            Some(Filtered::Rule(rule("name=ghost*"))),
+            "user rules count too"
+        );
+    }
+}
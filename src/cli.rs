@@ -0,0 +1,502 @@
+//! Running without a window, so sweeps can be bound to your own shortcuts, scripts and
+//! logout hooks. Same lists, filters and escalation as the window, read from what it saved.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::Expurgate;
+use crate::app::strip_file_extension;
+use crate::backend::WindowId;
+use crate::dry_run::{Candidate, Decision, candidates};
+use crate::rules::Rule;
+use crate::ruleset::RuleSet;
+use crate::session::LAST_SESSION;
+
+/// Everything closed, or there was nothing to close.
+pub const SUCCESS: u8 = 0;
+/// Some apps are still around after being asked, some didn't start again, or the output
+/// or the config couldn't be written.
+pub const SURVIVORS: u8 = 1;
+/// Bad arguments, an invalid pattern, no such profile or a rule set that doesn't read.
+/// Clap exits with it too.
+pub const USAGE: u8 = 2;
+/// `close` didn't find anything its pattern matches, or `restore` has no such snapshot.
+pub const NO_MATCH: u8 = 3;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "expurgate",
+    version,
+    about = "Closes every app you didn't ask to keep."
+)]
+pub struct Cli {
+    /// Opens the window when there's none.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Which of the saved lists to go by.
+#[derive(Debug, Args)]
+pub struct Lists {
+    /// This profile's lists instead of the active one's.
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// Also the killlist, like "Kill them all." does.
+    #[arg(long)]
+    pub killlist: bool,
+}
+
+/// Looking before leaping.
+#[derive(Debug, Args)]
+pub struct DryRun {
+    /// Shows every window and what would happen to it and why, closing nothing.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// JSON instead of a table.
+    #[arg(long, requires = "dry_run")]
+    pub json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Lists the windows a sweep would close.
+    List {
+        #[command(flatten)]
+        lists: Lists,
+        /// JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Sweeps with the saved lists, like the hotkey does, and waits to see how it went.
+    Sweep {
+        #[command(flatten)]
+        lists: Lists,
+        #[command(flatten)]
+        dry_run: DryRun,
+    },
+    /// Closes everything a rule matches, e.g. `firefox` or `title=*YouTube*`.
+    Close {
+        /// A process name, or field=glob / field~=regex on name, path, title, class or args.
+        pattern: String,
+        /// Closes them with this profile's strategy instead of the active one's.
+        #[arg(long)]
+        profile: Option<String>,
+        #[command(flatten)]
+        dry_run: DryRun,
+    },
+    /// Starts the apps a sweep closed again.
+    Restore {
+        /// A snapshot kept under a name, the last sweep's if there's none.
+        name: Option<String>,
+    },
+    /// Lists the kept snapshots.
+    Sessions,
+    /// Writes a profile's allowlist and killlist to a file to share, comments and all.
+    Export {
+        path: PathBuf,
+        /// This profile's lists instead of the active one's.
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Adds a shared rule set to a profile's lists, keeping yours where the two disagree.
+    Import {
+        path: PathBuf,
+        /// Into this profile instead of the active one.
+        #[arg(long)]
+        profile: Option<String>,
+        /// Makes the lists exactly what's in the file instead.
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
+/// One window and what happens to it.
+#[derive(Debug, serde::Serialize)]
+struct Row<'a> {
+    pid: u32,
+    name: &'a str,
+    window: WindowId,
+    title: &'a str,
+    class: &'a str,
+    decision: &'static str,
+    /// The rule behind the decision, if there was one.
+    rule: Option<String>,
+}
+
+fn rows(candidates: &[Candidate]) -> Vec<Row<'_>> {
+    candidates
+        .iter()
+        .map(|candidate| Row {
+            pid: candidate.pid,
+            name: &candidate.name,
+            window: candidate.window.id,
+            title: &candidate.window.title,
+            class: &candidate.window.class,
+            decision: candidate.decision.kind(),
+            rule: candidate.decision.rule(),
+        })
+        .collect()
+}
+
+fn print(out: &mut dyn Write, candidates: &[Candidate], json: bool) -> io::Result<()> {
+    let rows = rows(candidates);
+    if json {
+        serde_json::to_writer_pretty(&mut *out, &rows)?;
+        return writeln!(out);
+    }
+
+    writeln!(
+        out,
+        "{:>8}  {:<24}  {:>10}  {:<14}  {:<20}  TITLE",
+        "PID", "NAME", "WINDOW", "DECISION", "RULE"
+    )?;
+    for row in rows {
+        writeln!(
+            out,
+            "{:>8}  {:<24}  {:>#10x}  {:<14}  {:<20}  {}",
+            row.pid,
+            strip_file_extension(row.name),
+            row.window,
+            row.decision,
+            row.rule.as_deref().unwrap_or("-"),
+            row.title
+        )?;
+    }
+    Ok(())
+}
+
+// the named profile, or the active one. complains if there's no such profile
+fn profile_name(app: &Expurgate, name: Option<String>) -> Option<String> {
+    match name {
+        Some(name) if app.profiles().get(&name).is_none() => {
+            eprintln!("expurgate: no profile called {name:?}");
+            None
+        }
+        Some(name) => Some(name),
+        None => Some(app.profiles().active_name().to_owned()),
+    }
+}
+
+// waits for the escalation, then one line per app and the summary
+fn report(app: &mut Expurgate, out: &mut dyn Write) -> io::Result<u8> {
+    let Some(report) = app.finish_sweep() else {
+        return Ok(SUCCESS);
+    };
+    for (target, outcome) in &report.results {
+        writeln!(
+            out,
+            "{} ({}): {}",
+            strip_file_extension(&target.name),
+            target.pid,
+            outcome.describe()
+        )?;
+    }
+    writeln!(out, "{}", report.summary())?;
+    Ok(if report.survivors().next().is_some() {
+        SURVIVORS
+    } else {
+        SUCCESS
+    })
+}
+
+// adds what's new and says so, or replaces the lists
+fn import(
+    app: &mut Expurgate,
+    path: &Path,
+    profile: Option<String>,
+    replace: bool,
+    out: &mut dyn Write,
+) -> io::Result<u8> {
+    let set = match RuleSet::load(path) {
+        Ok(set) => set,
+        Err(e) => {
+            eprintln!("expurgate: {}: {e}", path.display());
+            return Ok(USAGE);
+        }
+    };
+    let Some(name) = profile_name(app, profile) else {
+        return Ok(USAGE);
+    };
+    // nothing to say about what was added if it didn't stick
+    let Some(merge) = app.import_rules(&name, &set, replace)? else {
+        return Ok(USAGE);
+    };
+    if replace {
+        return Ok(SUCCESS);
+    }
+    for (list, entry) in &merge.added {
+        writeln!(out, "added {} to the {}", entry.rule, list.label())?;
+    }
+    for conflict in &merge.conflicts {
+        writeln!(
+            out,
+            "kept {}: yours is {}, theirs {}",
+            conflict.rule, conflict.ours, conflict.theirs
+        )?;
+    }
+    Ok(SUCCESS)
+}
+
+fn execute(app: &mut Expurgate, command: Command, out: &mut dyn Write) -> io::Result<u8> {
+    match command {
+        Command::List { lists, json } => {
+            let Some((_, profile)) = profile_name(app, lists.profile)
+                .and_then(|name| app.profiles().get(&name).map(|profile| (name, profile)))
+            else {
+                return Ok(USAGE);
+            };
+            let mut dry_run = app.dry_run(profile, lists.killlist, None);
+            dry_run.retain(|candidate| candidate.decision.closes());
+            print(out, &dry_run, json)?;
+            Ok(SUCCESS)
+        }
+        Command::Sweep { lists, dry_run } => {
+            let Some((name, profile)) = profile_name(app, lists.profile)
+                .and_then(|name| app.profiles().get(&name).map(|profile| (name, profile)))
+            else {
+                return Ok(USAGE);
+            };
+            if dry_run.dry_run {
+                print(
+                    out,
+                    &app.dry_run(profile, lists.killlist, None),
+                    dry_run.json,
+                )?;
+                return Ok(SUCCESS);
+            }
+            let list = app.sweep_targets(profile, lists.killlist, None);
+            app.close_list(&name, &list);
+            report(app, out)
+        }
+        Command::Close {
+            pattern,
+            profile,
+            dry_run,
+        } => {
+            let rule = match pattern.parse::<Rule>() {
+                Ok(rule) => rule,
+                Err(e) => {
+                    eprintln!("expurgate: {e}");
+                    return Ok(USAGE);
+                }
+            };
+            let Some(name) = profile_name(app, profile) else {
+                return Ok(USAGE);
+            };
+            let list = app.matching(&rule);
+            if list.is_empty() {
+                eprintln!("expurgate: nothing matches {rule}");
+                return Ok(NO_MATCH);
+            }
+            if dry_run.dry_run {
+                let decision = Decision::Matched(rule);
+                let matched: Vec<_> = list
+                    .iter()
+                    .flat_map(|(name, pid, process)| candidates(name, *pid, process, &decision))
+                    .collect();
+                print(out, &matched, dry_run.json)?;
+                return Ok(SUCCESS);
+            }
+            app.close_list(&name, &list);
+            report(app, out)
+        }
+        Command::Restore { name } => {
+            let name = name.as_deref().unwrap_or(LAST_SESSION);
+            let Some(failed) = app.restore_session(name) else {
+                eprintln!("expurgate: nothing saved as {name:?}");
+                return Ok(NO_MATCH);
+            };
+            for (app, e) in &failed {
+                eprintln!("expurgate: couldn't start {app}: {e}");
+            }
+            Ok(if failed.is_empty() {
+                SUCCESS
+            } else {
+                SURVIVORS
+            })
+        }
+        Command::Sessions => {
+            for (name, snapshot) in &app.sessions().snapshots {
+                let apps: Vec<_> = snapshot
+                    .apps
+                    .iter()
+                    .map(|app| strip_file_extension(&app.name))
+                    .collect();
+                writeln!(out, "{name}: {}", apps.join(", "))?;
+            }
+            Ok(SUCCESS)
+        }
+        Command::Export { path, profile } => {
+            let set = profile_name(app, profile).and_then(|name| app.export_rules(&name));
+            set.map_or(Ok(USAGE), |set| set.save(&path).map(|()| SUCCESS))
+        }
+        Command::Import {
+            path,
+            profile,
+            replace,
+        } => import(app, &path, profile, replace, out),
+    }
+}
+
+// release builds are a windows subsystem app, without this nothing we print shows up
+#[cfg(windows)]
+#[allow(unsafe_code)]
+fn attach_console() {
+    use windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+
+    // SAFETY: no pointers involved, failing just means there's no console to print to
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) }.ok();
+}
+
+/// Runs `command` against the saved state and what's open right now.
+pub fn run(command: Command) -> ExitCode {
+    #[cfg(windows)]
+    attach_console();
+
+    let mut app = Expurgate::load_saved();
+    app.refresh_processlist();
+    match execute(&mut app, command, &mut io::stdout().lock()) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("expurgate: {e}");
+            ExitCode::from(SURVIVORS)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory as _, Parser as _};
+
+    use super::{Cli, Command, NO_MATCH, SUCCESS, USAGE, execute};
+    use crate::backend::mock::MockBackend;
+    use crate::ruleset::RuleSet;
+
+    fn command(args: &[&str]) -> Command {
+        Cli::try_parse_from(std::iter::once(&"expurgate").chain(args))
+            .expect("valid arguments")
+            .command
+            .expect("a subcommand")
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn no_subcommand_opens_the_window() {
+        let cli = Cli::try_parse_from(["expurgate"]).expect("valid arguments");
+        assert!(cli.command.is_none(), "nothing to run headless");
+    }
+
+    #[test]
+    fn list_prints_what_a_sweep_would_close() {
+        let (mut app, _) = MockBackend::desk().app();
+        let mut out = Vec::new();
+        let code = execute(&mut app, command(&["list", "--json"]), &mut out).expect("writes");
+        assert_eq!(code, SUCCESS, "listing always works");
+
+        let rows: serde_json::Value = serde_json::from_slice(&out).expect("valid json");
+        let names: Vec<_> = rows
+            .as_array()
+            .expect("an array of windows")
+            .iter()
+            .map(|row| row["name"].as_str().expect("a name"))
+            .collect();
+        assert_eq!(names, ["code.exe", "notepad.exe"], "SearchHost is filtered");
+    }
+
+    #[test]
+    fn close_by_pattern() {
+        let (mut app, mock) = MockBackend::desk().app();
+        let code = execute(
+            &mut app,
+            command(&["close", "title=*Notepad"]),
+            &mut Vec::new(),
+        )
+        .expect("writes");
+        assert_eq!(code, SUCCESS, "notepad was asked to close");
+        assert_eq!(mock.closed(), [2], "and nothing else");
+    }
+
+    #[test]
+    fn dry_runs_say_why_and_close_nothing() {
+        let (mut app, mock) = MockBackend::desk().app();
+        let mut out = Vec::new();
+        let code = execute(
+            &mut app,
+            command(&["sweep", "--dry-run", "--json"]),
+            &mut out,
+        )
+        .expect("writes");
+        assert_eq!(code, SUCCESS, "a dry run always works");
+        assert!(mock.closed().is_empty(), "nothing was touched");
+
+        let rows: serde_json::Value = serde_json::from_slice(&out).expect("valid json");
+        let search = rows
+            .as_array()
+            .expect("an array of windows")
+            .iter()
+            .find(|row| row["name"] == "SearchHost.exe")
+            .expect("filtered windows are listed too");
+        assert_eq!(search["decision"], "filtered", "it's a helper");
+        assert_eq!(search["rule"], "name=*host*", "caught by this rule");
+    }
+
+    #[test]
+    fn exit_codes_tell_what_went_wrong() {
+        let (mut app, mock) = MockBackend::desk().app();
+        for (args, expected) in [
+            (&["close", "firefox"][..], NO_MATCH),
+            (&["close", "title~=("], USAGE),
+            (&["sweep", "--profile", "nope"], USAGE),
+        ] {
+            let code = execute(&mut app, command(args), &mut Vec::new()).expect("writes");
+            assert_eq!(code, expected, "{args:?}");
+        }
+        assert!(mock.closed().is_empty(), "nothing closed along the way");
+
+        let code =
+            execute(&mut app, command(&["restore", "monday"]), &mut Vec::new()).expect("writes");
+        assert_eq!(code, NO_MATCH, "nothing kept as monday");
+    }
+
+    #[test]
+    fn rule_sets_go_from_one_desk_to_another() {
+        let dir = std::env::temp_dir().join(format!("expurgate-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let path = dir.join("team.toml");
+        let path = path.to_str().expect("utf-8 temp dir");
+
+        let (mut mine, _) = MockBackend::desk().app();
+        let code = execute(&mut mine, command(&["import", path]), &mut Vec::new()).expect("writes");
+        assert_eq!(code, USAGE, "no file yet");
+        std::fs::write(
+            path,
+            "[[allowlist]]\nrule = \"code.exe\"\ncomment = \"everyone's IDE\"\n",
+        )
+        .expect("writes");
+
+        let mut out = Vec::new();
+        let code = execute(&mut mine, command(&["import", path]), &mut out).expect("writes");
+        assert_eq!(code, SUCCESS, "it reads");
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "added code.exe to the allowlist\n",
+            "says what it did"
+        );
+
+        let code = execute(&mut mine, command(&["export", path]), &mut Vec::new()).expect("writes");
+        assert_eq!(code, SUCCESS, "and writes");
+        let exported = RuleSet::load(path.as_ref()).expect("exported");
+        assert_eq!(
+            exported.allowlist[0].comment, "everyone's IDE",
+            "with the comment"
+        );
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,554 @@
+//! What happens after we've asked nicely: wait, check whether the process is still around,
+//! then optionally terminate and finally force kill it.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System};
+
+use crate::backend::{WindowBackend, WindowId};
+use crate::processes::{ToClose, close_targets};
+use crate::profiles::Profile;
+use crate::session::SavedApp;
+
+/// How hard to try closing an app. There's a global one and per-app overrides.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CloseStrategy {
+    /// Seconds to wait after asking the windows to close.
+    pub grace_secs: u64,
+    /// Ask the process to terminate (SIGTERM) if it's still there. Not a thing on Windows.
+    pub terminate: bool,
+    /// Seconds to wait after terminating.
+    pub terminate_grace_secs: u64,
+    /// Kill whatever is still left. Unsaved work is gone, so off unless you really mean it.
+    pub force_kill: bool,
+}
+
+impl Default for CloseStrategy {
+    fn default() -> Self {
+        Self {
+            grace_secs: 5,
+            terminate: false,
+            terminate_grace_secs: 3,
+            force_kill: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Went away after being asked politely.
+    Closed,
+    Terminated,
+    Killed,
+    /// Went away, but a new one with the same name showed up right after (hi Steam).
+    Respawned,
+    /// Still running with a new window open, most likely "save changes?".
+    WaitingOnDialog,
+    /// Still running and didn't react, or went off to sit in the tray.
+    Ignored,
+}
+
+impl Outcome {
+    /// Whether the process is actually gone for good.
+    pub fn is_closed(self) -> bool {
+        matches!(self, Self::Closed | Self::Terminated | Self::Killed)
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Terminated => "terminated",
+            Self::Killed => "killed",
+            Self::Respawned => "came back",
+            Self::WaitingOnDialog => "waiting on a dialog",
+            Self::Ignored => "ignored us",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    AskedPolitely,
+    Terminated,
+    Killed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Terminate,
+    Kill,
+    /// It's gone, this is how.
+    Done(Outcome),
+    /// Still running after everything the strategy allowed.
+    GaveUp,
+}
+
+/// The escalation of one process, without any of the actual waiting or killing.
+#[derive(Clone, Debug)]
+pub struct Escalation {
+    strategy: CloseStrategy,
+    stage: Stage,
+}
+
+impl Escalation {
+    /// Starts right after the windows were asked to close.
+    pub fn new(strategy: CloseStrategy) -> Self {
+        Self {
+            strategy,
+            stage: Stage::AskedPolitely,
+        }
+    }
+
+    /// How long to give the process before checking on it again.
+    pub fn wait(&self) -> Duration {
+        match self.stage {
+            Stage::AskedPolitely => Duration::from_secs(self.strategy.grace_secs),
+            Stage::Terminated => Duration::from_secs(self.strategy.terminate_grace_secs),
+            // killing is immediate, this is just for the process table to catch up
+            Stage::Killed => Duration::from_secs(1),
+        }
+    }
+
+    /// What to do now that the wait is over, given whether the process is still alive.
+    pub fn next(&mut self, alive: bool) -> Action {
+        if !alive {
+            return Action::Done(match self.stage {
+                Stage::AskedPolitely => Outcome::Closed,
+                Stage::Terminated => Outcome::Terminated,
+                Stage::Killed => Outcome::Killed,
+            });
+        }
+
+        match self.stage {
+            Stage::AskedPolitely if self.strategy.terminate => {
+                self.stage = Stage::Terminated;
+                Action::Terminate
+            }
+            Stage::AskedPolitely | Stage::Terminated if self.strategy.force_kill => {
+                self.stage = Stage::Killed;
+                Action::Kill
+            }
+            _ => Action::GaveUp,
+        }
+    }
+}
+
+/// A process we asked to close and keep an eye on.
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub name: String,
+    pub pid: u32,
+    /// So we notice if the pid got reused by something else in the meantime.
+    pub start_time: u64,
+    pub strategy: CloseStrategy,
+    /// The windows we asked to close.
+    pub windows: Vec<WindowId>,
+    /// How to start it again, if we could see its executable.
+    pub saved: Option<SavedApp>,
+}
+
+fn is_alive(sys: &System, target: &Target) -> bool {
+    sys.process(Pid::from_u32(target.pid))
+        .is_some_and(|process| process.start_time() == target.start_time)
+}
+
+/// Why a process we gave up on is still around.
+pub fn survivor_outcome(backend: &dyn WindowBackend, target: &Target) -> Outcome {
+    let new_window = backend
+        .windows_of(target.pid)
+        .into_iter()
+        .any(|window| backend.is_visible(window) && !target.windows.contains(&window));
+    if new_window {
+        Outcome::WaitingOnDialog
+    } else {
+        Outcome::Ignored
+    }
+}
+
+// same name, started after the sweep did
+fn respawned(sys: &System, target: &Target, since: u64) -> bool {
+    sys.processes().iter().any(|(pid, process)| {
+        pid.as_u32() != target.pid
+            && process.start_time() >= since
+            && process.name().to_string_lossy() == target.name
+    })
+}
+
+/// How a sweep went, filled in as the results come in.
+#[derive(Clone, Debug, Default)]
+pub struct SweepReport {
+    pub results: Vec<(Target, Outcome)>,
+    /// Targets we're still waiting on.
+    pub pending: usize,
+}
+
+impl SweepReport {
+    pub fn new(targets: usize) -> Self {
+        Self {
+            results: Vec::new(),
+            pending: targets,
+        }
+    }
+
+    pub fn record(&mut self, target: Target, outcome: Outcome) {
+        self.results.push((target, outcome));
+        self.pending = self.pending.saturating_sub(1);
+    }
+
+    /// Everything that's still around, the stuff you have to go deal with yourself.
+    pub fn survivors(&self) -> impl Iterator<Item = &(Target, Outcome)> {
+        self.results
+            .iter()
+            .filter(|(_, outcome)| !outcome.is_closed())
+    }
+
+    /// What went away, however hard we had to push.
+    pub fn closed(&self) -> impl Iterator<Item = &Target> {
+        self.results
+            .iter()
+            .filter(|(_, outcome)| outcome.is_closed())
+            .map(|(target, _)| target)
+    }
+
+    /// One line, for the tray tooltip.
+    pub fn summary(&self) -> String {
+        let closed = self.closed().count();
+        let mut summary = format!("{closed} closed");
+        for outcome in [
+            Outcome::WaitingOnDialog,
+            Outcome::Respawned,
+            Outcome::Ignored,
+        ] {
+            let count = self.survivors().filter(|(_, o)| *o == outcome).count();
+            if count > 0 {
+                summary.push_str(&format!(", {count} {}", outcome.describe()));
+            }
+        }
+        if self.pending > 0 {
+            summary.push_str(&format!(", {} pending", self.pending));
+        }
+        summary
+    }
+}
+
+/// The profile's strategy for `name`, without any signals if the pid of one of its
+/// `windows` is only a guess.
+pub fn strategy_for(
+    backend: &dyn WindowBackend,
+    profile: &Profile,
+    name: &str,
+    windows: &[WindowId],
+) -> CloseStrategy {
+    let mut strategy = profile.strategy_for(name).clone();
+    if windows
+        .iter()
+        .any(|window| backend.owner_is_guessed(*window))
+    {
+        strategy.terminate = false;
+        strategy.force_kill = false;
+    }
+    strategy
+}
+
+/// Asks everything in `to_close` to close with the strategies of `profile` and escalates
+/// in the background. Gives back who's being watched and where their outcomes come in.
+pub fn start_sweep(
+    backend: &Arc<dyn WindowBackend>,
+    profile: &Profile,
+    to_close: &ToClose,
+) -> (Vec<Target>, Receiver<(Target, Outcome)>) {
+    let mut targets = Vec::new();
+    for (name, pid, process) in to_close {
+        // can't watch (or bring back) what sysinfo doesn't know about
+        if let Some(start_time) = process.info.start_time {
+            let titles = process
+                .windows
+                .iter()
+                .filter(|window| window.in_taskbar)
+                .map(|window| window.title.clone())
+                .collect();
+            let windows: Vec<_> = process.windows.iter().map(|window| window.id).collect();
+            targets.push(Target {
+                name: name.clone(),
+                pid: *pid,
+                start_time,
+                strategy: strategy_for(backend.as_ref(), profile, name, &windows),
+                windows,
+                saved: SavedApp::capture(&process.info, titles),
+            });
+        }
+        for window in close_targets(&process.windows) {
+            if let Err(e) = backend.close(window) {
+                log::warn!("Failed to close window {window}: {e}");
+            }
+        }
+    }
+
+    let (tx, rx) = mpsc::channel();
+    if !targets.is_empty() {
+        escalate(backend.clone(), targets.clone(), tx);
+    }
+    (targets, rx)
+}
+
+/// Runs the escalation of every target in the background, sending how each one went.
+pub fn escalate(
+    backend: Arc<dyn WindowBackend>,
+    targets: Vec<Target>,
+    results: Sender<(Target, Outcome)>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut sys = System::new();
+        let started = Instant::now();
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut pending: Vec<(Target, Escalation, Instant)> = targets
+            .into_iter()
+            .map(|target| {
+                let escalation = Escalation::new(target.strategy.clone());
+                let deadline = started + escalation.wait();
+                (target, escalation, deadline)
+            })
+            .collect();
+
+        while let Some(deadline) = pending.iter().map(|(_, _, deadline)| *deadline).min() {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+
+            let pids: Vec<Pid> = pending
+                .iter()
+                .map(|(target, _, _)| Pid::from_u32(target.pid))
+                .collect();
+            sys.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&pids),
+                true,
+                ProcessRefreshKind::nothing(),
+            );
+            // everything else too, for spotting respawns
+            sys.refresh_processes_specifics(
+                ProcessesToUpdate::All,
+                false,
+                ProcessRefreshKind::nothing(),
+            );
+
+            let now = Instant::now();
+            let mut still_pending = Vec::new();
+            for (target, mut escalation, deadline) in pending {
+                if deadline > now {
+                    still_pending.push((target, escalation, deadline));
+                    continue;
+                }
+
+                let mut alive = is_alive(&sys, &target);
+                loop {
+                    let process = sys.process(Pid::from_u32(target.pid));
+                    let sent = match escalation.next(alive) {
+                        Action::Terminate => {
+                            log::info!("Terminating {} ({})", target.name, target.pid);
+                            process.and_then(|p| p.kill_with(Signal::Term)) == Some(true)
+                        }
+                        Action::Kill => {
+                            log::info!("Force killing {} ({})", target.name, target.pid);
+                            process.is_some_and(|p| p.kill())
+                        }
+                        Action::Done(outcome) => {
+                            let outcome = if respawned(&sys, &target, since) {
+                                Outcome::Respawned
+                            } else {
+                                outcome
+                            };
+                            log::info!("{} ({}): {outcome:?}", target.name, target.pid);
+                            results.send((target, outcome)).ok();
+                            break;
+                        }
+                        Action::GaveUp => {
+                            let outcome = survivor_outcome(backend.as_ref(), &target);
+                            log::info!("{} ({}): {outcome:?}", target.name, target.pid);
+                            results.send((target, outcome)).ok();
+                            break;
+                        }
+                    };
+                    if sent {
+                        let deadline = now + escalation.wait();
+                        still_pending.push((target, escalation, deadline));
+                        break;
+                    }
+                    // couldn't send it (e.g. no SIGTERM on windows), straight to the next step
+                    alive = true;
+                }
+            }
+            pending = still_pending;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        Action, CloseStrategy, Escalation, Outcome, SweepReport, Target, strategy_for,
+        survivor_outcome,
+    };
+    use crate::backend::mock::{MockBackend, MockWindow};
+    use crate::profiles::Profile;
+
+    fn target(name: &str, pid: u32, windows: &[u64]) -> Target {
+        Target {
+            name: name.to_owned(),
+            pid,
+            start_time: 0,
+            strategy: CloseStrategy::default(),
+            windows: windows.to_vec(),
+            saved: None,
+        }
+    }
+
+    #[test]
+    fn default_never_kills() {
+        let mut escalation = Escalation::new(CloseStrategy::default());
+        assert_eq!(escalation.wait(), Duration::from_secs(5), "default grace");
+        assert_eq!(
+            escalation.next(true),
+            Action::GaveUp,
+            "nothing past the polite close unless enabled"
+        );
+    }
+
+    #[test]
+    fn closed_politely() {
+        let mut escalation = Escalation::new(CloseStrategy {
+            force_kill: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            escalation.next(false),
+            Action::Done(Outcome::Closed),
+            "gone before anything else happened"
+        );
+    }
+
+    #[test]
+    fn escalates_all_the_way() {
+        let mut escalation = Escalation::new(CloseStrategy {
+            grace_secs: 2,
+            terminate: true,
+            terminate_grace_secs: 7,
+            force_kill: true,
+        });
+        assert_eq!(escalation.wait(), Duration::from_secs(2), "grace first");
+        assert_eq!(escalation.next(true), Action::Terminate, "then terminate");
+        assert_eq!(escalation.wait(), Duration::from_secs(7), "terminate grace");
+        assert_eq!(escalation.next(true), Action::Kill, "then kill");
+        assert_eq!(
+            escalation.next(false),
+            Action::Done(Outcome::Killed),
+            "and it's dead"
+        );
+    }
+
+    #[test]
+    fn terminate_without_kill_gives_up() {
+        let mut escalation = Escalation::new(CloseStrategy {
+            terminate: true,
+            ..Default::default()
+        });
+        assert_eq!(escalation.next(true), Action::Terminate, "terminate");
+        assert_eq!(
+            escalation.next(true),
+            Action::GaveUp,
+            "no kill unless enabled"
+        );
+    }
+
+    #[test]
+    fn terminate_works() {
+        let mut escalation = Escalation::new(CloseStrategy {
+            terminate: true,
+            force_kill: true,
+            ..Default::default()
+        });
+        assert_eq!(escalation.next(true), Action::Terminate, "terminate");
+        assert_eq!(
+            escalation.next(false),
+            Action::Done(Outcome::Terminated),
+            "gone after terminating"
+        );
+    }
+
+    #[test]
+    fn new_window_means_a_dialog() {
+        let mock = MockBackend::new()
+            .process(1, "notepad.exe")
+            .window(MockWindow::new(10, 1))
+            .window(MockWindow::new(11, 1).owned_by(10).title("Save changes?"));
+        assert_eq!(
+            survivor_outcome(&mock, &target("notepad.exe", 1, &[10])),
+            Outcome::WaitingOnDialog,
+            "the save prompt is new"
+        );
+    }
+
+    #[test]
+    fn same_windows_means_ignored() {
+        let mock = MockBackend::new()
+            .process(1, "stubborn.exe")
+            .window(MockWindow::new(10, 1))
+            .window(MockWindow::new(11, 1).hidden());
+        assert_eq!(
+            survivor_outcome(&mock, &target("stubborn.exe", 1, &[10])),
+            Outcome::Ignored,
+            "nothing new showed up"
+        );
+    }
+
+    #[test]
+    fn guessed_pids_only_get_their_windows_closed() {
+        let mock = MockBackend::new()
+            .window(MockWindow::new(10, 1))
+            .window(MockWindow::new(20, 2).guessed());
+        let profile = Profile {
+            close_strategy: CloseStrategy {
+                terminate: true,
+                force_kill: true,
+                ..CloseStrategy::default()
+            },
+            ..Profile::default()
+        };
+        assert!(
+            strategy_for(&mock, &profile, "firefox", &[10]).force_kill,
+            "we know whose that is"
+        );
+        let guessed = strategy_for(&mock, &profile, "nautilus", &[20]);
+        assert!(
+            !guessed.terminate && !guessed.force_kill,
+            "might be someone else's"
+        );
+    }
+
+    #[test]
+    fn report_summary() {
+        let mut report = SweepReport::new(5);
+        report.record(target("a.exe", 1, &[]), Outcome::Closed);
+        report.record(target("b.exe", 2, &[]), Outcome::Killed);
+        report.record(target("c.exe", 3, &[]), Outcome::WaitingOnDialog);
+        report.record(target("d.exe", 4, &[]), Outcome::Respawned);
+        assert_eq!(
+            report.summary(),
+            "2 closed, 1 waiting on a dialog, 1 came back, 1 pending",
+            "counts by outcome"
+        );
+        assert_eq!(
+            report
+                .survivors()
+                .map(|(target, _)| target.name.as_str())
+                .collect::<Vec<_>>(),
+            ["c.exe", "d.exe"],
+            "the ones you have to deal with"
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Everything you'd set up once, in a TOML file that can live in a dotfiles repo.
+//!
+//! It's in the config directory (`$XDG_CONFIG_HOME`, `%APPDATA%` on Windows), written
+//! once something changed in the window and it's been left alone for a moment, and read
+//! again whenever something else changes it. Files from before there was one get brought
+//! up to date by `version`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::classifier::Classifier;
+use crate::hotkey::{Action, Hotkey};
+use crate::profiles::Profiles;
+use crate::scheduler::ScheduledSweep;
+use crate::watch::Watch;
+
+/// What [`Config::version`] is for files written by this build.
+pub const VERSION: u32 = 1;
+
+// how often it looks whether someone else changed the file
+const POLL: Duration = Duration::from_secs(1);
+
+// our own writes it knows to skip, a few in case it's slow to look
+const OURS: usize = 8;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// Written by a newer version, which might've added things we'd lose.
+    TooNew(u32),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::Parse(e) => e.fmt(f),
+            Self::TooNew(version) => write!(
+                f,
+                "version {version} is newer than this expurgate knows ({VERSION})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Parse(e)
+    }
+}
+
+/// Profiles with their rules and close strategies, hotkeys and everything else that isn't
+/// what happens to be open right now.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    pub profiles: Profiles,
+    pub classifier: Classifier,
+    /// Hotkeys for everything that isn't sweeping with a given profile.
+    pub hotkeys: BTreeMap<Action, Hotkey>,
+    pub show_all_processes: bool,
+    pub snapshot_sweeps: bool,
+    pub undo_secs: u64,
+    pub watch: Watch,
+    pub scheduled: Vec<ScheduledSweep>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            profiles: Profiles::default(),
+            classifier: Classifier::default(),
+            hotkeys: BTreeMap::new(),
+            show_all_processes: false,
+            snapshot_sweeps: true,
+            undo_secs: 60,
+            watch: Watch::default(),
+            scheduled: Vec::new(),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(not(windows))]
+fn config_dir() -> Option<PathBuf> {
+    // relative ones are to be ignored, says the spec
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("expurgate").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn to_toml(&self) -> io::Result<String> {
+        // through a `Value`, that one knows to put the tables last and takes enums as keys
+        let value = toml::Value::try_from(self).map_err(io::Error::other)?;
+        toml::to_string_pretty(&value).map_err(io::Error::other)
+    }
+
+    /// Replaces the file in one go, so nobody reads half of it. Symlinks stay symlinks.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write(path, &self.to_toml()?)
+    }
+}
+
+fn write(path: &Path, text: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("toml.tmp");
+    fs::write(&temp, text)?;
+    fs::rename(&temp, &path)
+}
+
+impl std::str::FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // through a `Value` as well, so the hotkeys' keys can be enums
+        let mut config: Self = toml::from_str::<toml::Value>(text)?.try_into()?;
+        if config.version > VERSION {
+            return Err(ConfigError::TooNew(config.version));
+        }
+        // version 1 is the first, older ones get brought up to date here once there are any
+        config.version = VERSION;
+        config.profiles.repair();
+        Ok(config)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+/// Handle to the thread reading the file again when it changes, does nothing if there
+/// isn't one.
+#[derive(Default)]
+pub struct ConfigWatcher {
+    changed: Option<Receiver<Result<Config, ConfigError>>>,
+    // what we wrote ourselves, newest last
+    ours: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl ConfigWatcher {
+    /// `wake` is called with every change, to get the app to pick it up. Our own writes
+    /// through [`ConfigWatcher::save`] don't come back.
+    pub fn spawn(path: PathBuf, wake: impl Fn() + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let ours = Arc::new(Mutex::new(VecDeque::<String>::new()));
+        let written = ours.clone();
+        thread::spawn(move || {
+            let mut last = modified(&path);
+            loop {
+                thread::sleep(POLL);
+                let now = modified(&path);
+                // gone is fine, we'll write it again with the next change
+                if now == last || now.is_none() {
+                    continue;
+                }
+                last = now;
+                let text = fs::read_to_string(&path);
+                // an older one of ours would undo whatever changed in the window since
+                let mine = text.as_ref().is_ok_and(|text| {
+                    written
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .contains(text)
+                });
+                if mine {
+                    continue;
+                }
+                let reloaded = text
+                    .map_err(ConfigError::from)
+                    .and_then(|text| text.parse());
+                if tx.send(reloaded).is_err() {
+                    return;
+                }
+                wake();
+            }
+        });
+        Self {
+            changed: Some(rx),
+            ours,
+        }
+    }
+
+    /// Saves `config` to the file, knowing it's not a change from elsewhere when it sees it.
+    pub fn save(&self, config: &Config, path: &Path) -> io::Result<()> {
+        let text = config.to_toml()?;
+        {
+            let mut ours = self.ours.lock().unwrap_or_else(PoisonError::into_inner);
+            ours.push_back(text.clone());
+            if ours.len() > OURS {
+                ours.pop_front();
+            }
+        }
+        write(path, &text)
+    }
+
+    pub fn try_recv(&self) -> Option<Result<Config, ConfigError>> {
+        self.changed.as_ref()?.try_recv().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Config, ConfigError, ConfigWatcher};
+    use crate::close::CloseStrategy;
+    use crate::hotkey::{Action, Hotkey};
+    use crate::profiles::DEFAULT_PROFILE;
+    use crate::rules::Rule;
+
+    fn hotkey(text: &str) -> Hotkey {
+        text.parse().expect("valid hotkey")
+    }
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        let profile = config
+            .profiles
+            .get_mut(DEFAULT_PROFILE)
+            .expect("there's a default profile");
+        profile.allowlist.insert(Rule::name("code.exe"));
+        profile
+            .killlist
+            .insert("title~=.*Steam$".parse().expect("valid rule"));
+        profile.strategy_overrides.insert(
+            "firefox".to_owned(),
+            CloseStrategy {
+                force_kill: true,
+                ..CloseStrategy::default()
+            },
+        );
+        config.hotkeys.insert(Action::Undo, hotkey("Ctrl+Alt+Z"));
+        config.watch.blocked.insert(Rule::name("discord.exe"), 30);
+        config
+            .watch
+            .hours
+            .push("Mon-Fri 09:00-17:30".parse().expect("valid hours"));
+        config.scheduled.push(crate::scheduler::ScheduledSweep {
+            when: "idle 15".parse().expect("valid schedule"),
+            profile: DEFAULT_PROFILE.to_owned(),
+        });
+        config
+    }
+
+    #[test]
+    fn survives_a_round_trip_through_toml() {
+        let text = config().to_toml().expect("serializes");
+        assert!(text.contains("version = 1"), "versioned:\n{text}");
+        assert_eq!(text.parse::<Config>().ok(), Some(config()), "\n{text}");
+    }
+
+    #[test]
+    fn hand_written_files_fill_in_the_rest() {
+        let config: Config = r#"
+            [hotkeys]
+            ShowWindow = "Ctrl+Alt+E"
+
+            [profiles.profiles.default]
+            allowlist = ["code.exe"]
+        "#
+        .parse()
+        .expect("a valid config");
+        assert_eq!(
+            config.hotkeys.get(&Action::ShowWindow),
+            Some(&hotkey("Ctrl+Alt+E")),
+            "enums as keys"
+        );
+        assert!(config.snapshot_sweeps, "defaults for what's not there");
+        assert_eq!(
+            config.profiles.active_name(),
+            DEFAULT_PROFILE,
+            "and a profile to be in"
+        );
+        assert!(
+            matches!(
+                "version = 99".parse::<Config>(),
+                Err(ConfigError::TooNew(99))
+            ),
+            "newer ones are left alone"
+        );
+    }
+
+    #[test]
+    fn edits_from_elsewhere_get_picked_up() {
+        let dir = std::env::temp_dir().join(format!("expurgate-config-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        Config::default().save(&path).expect("saves");
+
+        let watcher = ConfigWatcher::spawn(path.clone(), || {});
+        // mtimes aren't that precise everywhere
+        std::thread::sleep(Duration::from_millis(1100));
+        config().save(&path).expect("saves");
+
+        let mut reloaded = None;
+        for _ in 0..50 {
+            reloaded = watcher.try_recv();
+            if reloaded.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(
+            reloaded.and_then(Result::ok),
+            Some(config()),
+            "read it again"
+        );
+        assert!(!dir.join("config.toml.tmp").exists(), "nothing left over");
+
+        std::thread::sleep(Duration::from_millis(1100));
+        watcher.save(&Config::default(), &path).expect("saves");
+        std::thread::sleep(Duration::from_millis(2100));
+        assert!(watcher.try_recv().is_none(), "our own write isn't news");
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
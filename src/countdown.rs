@@ -0,0 +1,151 @@
+//! A few seconds between a hotkey and its sweep, to back out of an accidental press.
+
+use std::time::{Duration, Instant};
+
+use crate::app::strip_file_extension;
+use crate::processes::{ToClose, close_targets};
+
+/// What the user made of the countdown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Choice {
+    Wait,
+    Cancel,
+    /// Now, or because time's up.
+    Confirm,
+}
+
+/// A sweep that's about to happen.
+pub struct Countdown {
+    pub profile: String,
+    /// Exactly what's shown, so nothing that opened in the meantime gets closed unseen.
+    pub to_close: ToClose,
+    deadline: Instant,
+    // name and title of every window that's going
+    windows: Vec<(String, String)>,
+    // the overlay takes focus once, so Esc and Enter reach it
+    focused: bool,
+}
+
+impl Countdown {
+    pub fn new(profile: &str, to_close: ToClose, delay: Duration) -> Self {
+        let windows = to_close
+            .iter()
+            .flat_map(|(name, _, process)| {
+                let targets = close_targets(&process.windows);
+                process
+                    .windows
+                    .iter()
+                    .filter(move |window| targets.contains(&window.id))
+                    .map(|window| (strip_file_extension(name), window.title.clone()))
+            })
+            .collect();
+        Self {
+            profile: profile.to_owned(),
+            to_close,
+            deadline: Instant::now() + delay,
+            windows,
+            focused: false,
+        }
+    }
+
+    /// Whole seconds left, rounded up so it doesn't say 0 while still waiting.
+    pub fn secs_left(&self, now: Instant) -> u64 {
+        let left = self.deadline.saturating_duration_since(now);
+        left.as_secs() + u64::from(left.subsec_nanos() > 0)
+    }
+
+    pub fn is_over(&self, now: Instant) -> bool {
+        now >= self.deadline
+    }
+
+    /// The always on top overlay with what's about to go, returns what the user did.
+    pub fn show(&mut self, ctx: &egui::Context) -> Choice {
+        let focus = !std::mem::replace(&mut self.focused, true);
+        let title = format!(
+            "Closing {} windows in {}…",
+            self.windows.len(),
+            self.secs_left(Instant::now())
+        );
+
+        let choice = ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("countdown"),
+            egui::ViewportBuilder::default()
+                .with_title("expurgate")
+                .with_inner_size([320.0, 240.0])
+                .with_resizable(false)
+                .with_always_on_top(),
+            |ctx, _class| {
+                if focus {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                }
+
+                let mut choice = Choice::Wait;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading(&title);
+                    egui::ScrollArea::vertical()
+                        .max_height(140.0)
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            for (name, title) in &self.windows {
+                                ui.label(format!("{name}: {title}"));
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        if ui.button("Close now (Enter)").clicked() {
+                            choice = Choice::Confirm;
+                        }
+                        if ui.button("Cancel (Esc)").clicked() {
+                            choice = Choice::Cancel;
+                        }
+                    });
+                });
+
+                ctx.input(|i| {
+                    if i.key_pressed(egui::Key::Escape) || i.viewport().close_requested() {
+                        choice = Choice::Cancel;
+                    } else if i.key_pressed(egui::Key::Enter) {
+                        choice = Choice::Confirm;
+                    }
+                });
+                choice
+            },
+        );
+
+        if choice == Choice::Wait && self.is_over(Instant::now()) {
+            return Choice::Confirm;
+        }
+        // ticking down
+        ctx.request_repaint_after(Duration::from_millis(100));
+        choice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Countdown;
+    use crate::processes::ToClose;
+
+    #[test]
+    fn seconds_round_up() {
+        let countdown = Countdown::new("default", ToClose::new(), Duration::from_secs(3));
+        let now = Instant::now();
+        assert_eq!(countdown.secs_left(now), 3, "just started");
+        assert_eq!(
+            countdown.secs_left(now + Duration::from_millis(2500)),
+            1,
+            "half a second is still 1"
+        );
+        assert!(!countdown.is_over(now), "not yet");
+        assert!(
+            countdown.is_over(now + Duration::from_secs(3)),
+            "and then it is"
+        );
+        assert_eq!(
+            countdown.secs_left(now + Duration::from_secs(4)),
+            0,
+            "no less than nothing"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! What a sweep would do with every window and why, without closing anything.
+//!
+//! The sweep itself goes by the same decisions, so the preview can't drift from it.
+
+use std::fmt;
+
+use crate::app::strip_file_extension;
+use crate::classifier::{Classifier, Filtered};
+use crate::processes::{ProcessList, ToClose, TrackedProcess, TrackedWindow, close_targets};
+use crate::profiles::Profile;
+use crate::rules::{Rule, first_match};
+
+// not closing ourselves, that'd be silly
+pub fn is_self(name: &str) -> bool {
+    strip_file_extension(name) == "expurgate"
+}
+
+/// What a sweep does with a process, or one of its windows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Close,
+    /// Closed because it's on the killlist, whatever the other lists say.
+    Killlisted(Rule),
+    /// Closed by name or pattern, e.g. `expurgate close firefox`.
+    Matched(Rule),
+    Allowlisted(Rule),
+    /// The classifier doesn't think it's a real app.
+    Filtered(Filtered),
+    /// Nothing in the taskbar, or a window next to the ones that get closed.
+    NotOnTaskbar,
+    /// Expurgate itself.
+    Ourselves,
+    /// The focused app, left open by sweeping everything but it.
+    Spared,
+}
+
+impl Decision {
+    pub fn closes(&self) -> bool {
+        matches!(self, Self::Close | Self::Killlisted(_) | Self::Matched(_))
+    }
+
+    /// Short and stable, for scripts.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Close => "close",
+            Self::Killlisted(_) => "killlisted",
+            Self::Matched(_) => "matched",
+            Self::Allowlisted(_) => "allowlisted",
+            Self::Filtered(_) => "filtered",
+            Self::NotOnTaskbar => "not_on_taskbar",
+            Self::Ourselves => "self",
+            Self::Spared => "spared",
+        }
+    }
+
+    /// The rule that made the decision, if it was one.
+    pub fn rule(&self) -> Option<String> {
+        match self {
+            Self::Killlisted(rule) | Self::Matched(rule) | Self::Allowlisted(rule) => {
+                Some(rule.to_string())
+            }
+            Self::Filtered(reason) => Some(reason.to_string()),
+            Self::Close | Self::NotOnTaskbar | Self::Ourselves | Self::Spared => None,
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Close => f.write_str("closed"),
+            Self::Killlisted(rule) => write!(f, "closed, killlisted by {rule}"),
+            Self::Matched(rule) => write!(f, "closed, matches {rule}"),
+            Self::Allowlisted(rule) => write!(f, "kept, allowlisted by {rule}"),
+            Self::Filtered(reason) => write!(f, "kept, filtered by {reason}"),
+            Self::NotOnTaskbar => f.write_str("kept, not on the taskbar"),
+            Self::Ourselves => f.write_str("kept, that's us"),
+            Self::Spared => f.write_str("kept, it's focused"),
+        }
+    }
+}
+
+/// What the sweep with `profile` does with one process, `spare` is a pid to leave alone.
+pub fn decide(
+    profile: &Profile,
+    classifier: &Classifier,
+    with_killlist: bool,
+    spare: Option<u32>,
+    (name, pid, process): (&str, u32, &TrackedProcess),
+) -> Decision {
+    // ourselves, whatever we're called
+    if is_self(name) || pid == std::process::id() {
+        return Decision::Ourselves;
+    }
+    if spare == Some(pid) {
+        return Decision::Spared;
+    }
+    if let (true, Some(rule)) = (with_killlist, first_match(&profile.killlist, process)) {
+        return Decision::Killlisted(rule.clone());
+    }
+    if !process.windows.iter().any(|window| window.in_taskbar) {
+        return Decision::NotOnTaskbar;
+    }
+    if let Some(rule) = first_match(&profile.allowlist, process) {
+        return Decision::Allowlisted(rule.clone());
+    }
+    // making sure we don't try to kill some system process or helper
+    if let Some(reason) = classifier.filtered(pid, process) {
+        return Decision::Filtered(reason);
+    }
+    Decision::Close
+}
+
+/// What a sweep with `profile` closes out of `list`, `spare` is a pid to leave alone.
+pub fn sweep_targets(
+    list: &ProcessList,
+    profile: &Profile,
+    classifier: &Classifier,
+    with_killlist: bool,
+    spare: Option<u32>,
+) -> ToClose {
+    // the sweep first, then whatever only the killlist catches
+    let mut to_close = ToClose::new();
+    let mut killlisted = ToClose::new();
+    for (name, processes) in list {
+        for (pid, process) in processes {
+            match decide(
+                profile,
+                classifier,
+                with_killlist,
+                spare,
+                (name, *pid, process),
+            ) {
+                Decision::Close => to_close.push((name.clone(), *pid, process.clone())),
+                Decision::Killlisted(rule) => {
+                    log::info!("Killlisted: {name} ({rule})");
+                    killlisted.push((name.clone(), *pid, process.clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+    to_close.extend(killlisted);
+    to_close
+}
+
+/// One window and what the sweep does with it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub name: String,
+    pub pid: u32,
+    pub window: TrackedWindow,
+    pub decision: Decision,
+}
+
+/// The windows of a process worth showing, each with its own decision. Hidden windows
+/// are left out unless they'd get closed, there's plenty of those around.
+pub fn candidates(
+    name: &str,
+    pid: u32,
+    process: &TrackedProcess,
+    decision: &Decision,
+) -> Vec<Candidate> {
+    let targets = if decision.closes() {
+        close_targets(&process.windows)
+    } else {
+        Vec::new()
+    };
+    process
+        .windows
+        .iter()
+        .filter(|window| window.visible || targets.contains(&window.id))
+        .map(|window| Candidate {
+            name: name.to_owned(),
+            pid,
+            window: window.clone(),
+            decision: if !decision.closes() || targets.contains(&window.id) {
+                decision.clone()
+            } else {
+                Decision::NotOnTaskbar
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decision, candidates, decide};
+    use crate::classifier::Classifier;
+    use crate::processes::{ProcessInfo, TrackedProcess, TrackedWindow};
+    use crate::profiles::Profile;
+    use crate::rules::Rule;
+
+    fn window(id: u64, in_taskbar: bool) -> TrackedWindow {
+        TrackedWindow {
+            id,
+            title: String::new(),
+            class: String::new(),
+            visible: true,
+            in_taskbar,
+        }
+    }
+
+    fn process(name: &str, windows: Vec<TrackedWindow>) -> TrackedProcess {
+        TrackedProcess {
+            info: ProcessInfo::new(name),
+            windows,
+        }
+    }
+
+    #[test]
+    fn the_killlist_beats_the_allowlist() {
+        let figma = process("Figma.exe", vec![window(1, false)]);
+        let profile = Profile {
+            allowlist: [Rule::name("Figma.exe")].into(),
+            killlist: [Rule::name("Figma.exe")].into(),
+            ..Profile::default()
+        };
+        let classifier = Classifier::default();
+
+        assert_eq!(
+            decide(&profile, &classifier, false, None, ("Figma.exe", 5, &figma)),
+            Decision::NotOnTaskbar,
+            "the hotkey doesn't see it"
+        );
+        assert_eq!(
+            decide(&profile, &classifier, true, None, ("Figma.exe", 5, &figma)),
+            Decision::Killlisted(Rule::name("Figma.exe")),
+            "the button does"
+        );
+        assert_eq!(
+            decide(
+                &profile,
+                &classifier,
+                true,
+                Some(5),
+                ("Figma.exe", 5, &figma)
+            ),
+            Decision::Spared,
+            "unless it's focused"
+        );
+    }
+
+    #[test]
+    fn side_windows_of_a_closed_app_are_kept() {
+        let code = process("code.exe", vec![window(1, true), window(2, false)]);
+        let decisions: Vec<_> = candidates("code.exe", 100, &code, &Decision::Close)
+            .into_iter()
+            .map(|candidate| (candidate.window.id, candidate.decision))
+            .collect();
+        assert_eq!(
+            decisions,
+            [(1, Decision::Close), (2, Decision::NotOnTaskbar)],
+            "only the taskbar window is asked to close"
+        );
+    }
+}
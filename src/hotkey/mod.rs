@@ -0,0 +1,369 @@
+//! Global hotkeys, through `RegisterHotKey` on Windows, key grabs on X11 and the
+//! `GlobalShortcuts` portal on Wayland. They all end up as a [`HotkeyEvent`] on a channel.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+#[cfg(windows)]
+mod win32;
+
+#[cfg(target_os = "linux")]
+mod x11;
+
+#[cfg(target_os = "linux")]
+mod portal;
+
+/// Things a hotkey can do apart from sweeping with a given profile.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+pub enum Action {
+    /// Sweep with the active profile.
+    Sweep,
+    /// The same, but whatever has focus stays open.
+    SweepExceptFocused,
+    ShowWindow,
+    /// Switch to the next profile.
+    NextProfile,
+    /// Start what the last sweep closed again, for a while after it.
+    Undo,
+}
+
+impl Action {
+    pub const ALL: [Self; 5] = [
+        Self::Sweep,
+        Self::SweepExceptFocused,
+        Self::ShowWindow,
+        Self::NextProfile,
+        Self::Undo,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Sweep => "Sweep",
+            Self::SweepExceptFocused => "Sweep all but the focused app",
+            Self::ShowWindow => "Show this window",
+            Self::NextProfile => "Next profile",
+            Self::Undo => "Undo the last sweep",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HotkeyEvent {
+    /// Sweep with the lists of this profile.
+    Kill {
+        profile: String,
+    },
+    Action(Action),
+}
+
+/// Why a hotkey didn't get registered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegisterError {
+    /// Some other app has it already.
+    Taken,
+    /// No global hotkeys here (yet).
+    Unsupported,
+    Other(String),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Taken => f.write_str("taken by another app"),
+            Self::Unsupported => f.write_str("no global hotkeys on this platform yet"),
+            Self::Other(e) => f.write_str(e),
+        }
+    }
+}
+
+/// The non-modifier part of a hotkey.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    /// A letter or digit, always uppercase.
+    Char(char),
+    /// F1 to F24.
+    F(u8),
+}
+
+/// A key combination like Ctrl+Alt+J, stored as that string.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hotkey {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// The Windows/Super key.
+    pub logo: bool,
+    pub key: Key,
+}
+
+impl Hotkey {
+    /// Ctrl+Alt+J, what it's always been.
+    pub const DEFAULT: Self = Self {
+        ctrl: true,
+        alt: true,
+        shift: false,
+        logo: false,
+        key: Key::Char('J'),
+    };
+
+    /// Similar combinations to offer when this one is taken, same key, other modifiers.
+    pub fn alternatives(self) -> Vec<Self> {
+        // (ctrl, alt, shift, logo)
+        let modifiers = [
+            (true, true, false, false),
+            (true, false, true, false),
+            (false, true, true, false),
+            (true, true, true, false),
+            (false, true, false, true),
+            (false, false, true, true),
+            (true, false, false, true),
+        ];
+        modifiers
+            .into_iter()
+            .map(|(ctrl, alt, shift, logo)| Self {
+                ctrl,
+                alt,
+                shift,
+                logo,
+                key: self.key,
+            })
+            .filter(|hotkey| *hotkey != self)
+            .collect()
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+            (self.logo, "Super+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        match self.key {
+            Key::Char(c) => write!(f, "{c}"),
+            Key::F(n) => write!(f, "F{n}"),
+        }
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hotkey = Self {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            key: Key::Char(' '),
+        };
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default().to_ascii_uppercase();
+
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => hotkey.ctrl = true,
+                "alt" => hotkey.alt = true,
+                "shift" => hotkey.shift = true,
+                "super" | "win" | "meta" | "logo" => hotkey.logo = true,
+                _ => return Err(format!("unknown modifier {part:?}")),
+            }
+        }
+
+        let mut chars = key.chars();
+        hotkey.key = match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphanumeric() => Key::Char(c),
+            (Some('F'), Some(_)) => match key[1..].parse() {
+                Ok(n @ 1..=24) => Key::F(n),
+                _ => return Err(format!("unknown key {key:?}")),
+            },
+            _ => return Err(format!("unknown key {key:?}")),
+        };
+
+        if !(hotkey.ctrl || hotkey.alt || hotkey.logo) {
+            return Err("needs Ctrl, Alt or Super, or it'd eat normal typing".to_owned());
+        }
+        Ok(hotkey)
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Hotkey> for String {
+    fn from(hotkey: Hotkey) -> Self {
+        hotkey.to_string()
+    }
+}
+
+type Bindings = Vec<(Hotkey, HotkeyEvent)>;
+type Failures = Vec<(Hotkey, RegisterError)>;
+
+/// Called after every event, so the ui wakes up to look.
+type Wake = Arc<dyn Fn() + Send + Sync>;
+
+/// Gets what couldn't be bound when the desktop takes its time answering.
+type Answered = Arc<dyn Fn(Failures) + Send + Sync>;
+
+/// What each platform's way of getting hotkeys has to do.
+trait Listener: Send {
+    /// `None` if the answer comes later, through [`Answered`].
+    fn rebind(&self, bindings: Bindings) -> Option<Failures>;
+
+    fn is_free(&self, hotkey: Hotkey) -> bool;
+}
+
+/// Owns the registered hotkeys, which live on their own thread.
+pub struct HotkeyListener {
+    // `None` where there's no way to get global hotkeys
+    inner: Option<Box<dyn Listener>>,
+}
+
+impl HotkeyListener {
+    /// Replaces every registered hotkey, returns the ones that couldn't be registered.
+    /// `None` while the desktop is still deciding, its answer goes to `answered` then.
+    pub fn rebind(
+        &self,
+        bindings: Vec<(Hotkey, HotkeyEvent)>,
+    ) -> Option<Vec<(Hotkey, RegisterError)>> {
+        match &self.inner {
+            Some(listener) => listener.rebind(bindings),
+            None => Some(
+                bindings
+                    .into_iter()
+                    .map(|(hotkey, _)| (hotkey, RegisterError::Unsupported))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether nothing else has the hotkey right now, without keeping it.
+    pub fn is_free(&self, hotkey: Hotkey) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|listener| listener.is_free(hotkey))
+    }
+}
+
+fn native_listener(
+    tx: Sender<HotkeyEvent>,
+    wake: Wake,
+    answered: Answered,
+) -> Option<Box<dyn Listener>> {
+    #[cfg(windows)]
+    {
+        // windows always answers right away
+        drop(answered);
+        Some(Box::new(win32::start_hotkey_listener(tx, wake)))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // x11 grabs under xwayland only see keys while an x11 app has focus, so the portal goes first
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match zbus::blocking::Connection::session()
+                .and_then(|conn| portal::Portal::connect(&conn))
+            {
+                Ok(listener) => return Some(Box::new(listener.listen(tx, wake, answered))),
+                Err(e) => log::warn!("No GlobalShortcuts portal, trying X11: {e}"),
+            }
+        }
+        match x11::Listener::connect(None) {
+            Ok(listener) => Some(Box::new(listener.listen(tx, wake))),
+            Err(e) => {
+                log::warn!("Couldn't connect to X11, no hotkeys for us: {e}");
+                None
+            }
+        }
+    }
+    // no global hotkeys here yet, the button still works
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        drop((tx, wake, answered));
+        None
+    }
+}
+
+/// Starts listening for hotkeys, nothing's registered until [`HotkeyListener::rebind`].
+///
+/// `wake` is called after every event, so the ui can look, and `answered` with whatever
+/// couldn't be bound when that's only known later.
+pub fn start_hotkey_listener(
+    tx: Sender<HotkeyEvent>,
+    wake: impl Fn() + Send + Sync + 'static,
+    answered: impl Fn(Vec<(Hotkey, RegisterError)>) + Send + Sync + 'static,
+) -> HotkeyListener {
+    HotkeyListener {
+        inner: native_listener(tx, Arc::new(wake), Arc::new(answered)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hotkey, Key};
+
+    #[test]
+    fn hotkeys_round_trip() {
+        for text in ["Ctrl+Alt+J", "Ctrl+Shift+F12", "Super+1"] {
+            let hotkey = text
+                .parse::<Hotkey>()
+                .unwrap_or_else(|e| panic!("{text}: {e}"));
+            assert_eq!(hotkey.to_string(), text, "{text} comes back the same");
+        }
+        assert_eq!(
+            "ctrl + alt + j".parse(),
+            Ok(Hotkey::DEFAULT),
+            "case and spaces don't matter"
+        );
+        assert_eq!(
+            "Win+F1".parse::<Hotkey>().map(|hotkey| hotkey.key),
+            Ok(Key::F(1)),
+            "win is super"
+        );
+    }
+
+    #[test]
+    fn bad_hotkeys_are_rejected() {
+        for text in ["J", "Shift+J", "Ctrl+", "Ctrl+F25", "Hyper+J", "Ctrl+Enter"] {
+            assert!(text.parse::<Hotkey>().is_err(), "{text} shouldn't parse");
+        }
+    }
+
+    #[test]
+    fn alternatives_keep_the_key() {
+        let alternatives = Hotkey::DEFAULT.alternatives();
+        assert!(!alternatives.is_empty(), "there's always something");
+        assert!(
+            !alternatives.contains(&Hotkey::DEFAULT),
+            "not the one that's taken"
+        );
+        assert!(
+            alternatives
+                .iter()
+                .all(|hotkey| hotkey.key == Key::Char('J')),
+            "same key every time"
+        );
+        assert!(
+            alternatives
+                .iter()
+                .all(|hotkey| hotkey.to_string().parse() == Ok(*hotkey)),
+            "and all of them are valid"
+        );
+    }
+}
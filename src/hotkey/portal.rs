@@ -0,0 +1,479 @@
+//! Hotkeys through the `GlobalShortcuts` portal, the only way to get them on most Wayland
+//! compositors. Our hotkeys are only the preferred triggers, the desktop has the final say
+//! and may ask the user first.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{DynamicType, ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+use super::{Answered, Bindings, Failures, Hotkey, HotkeyEvent, Key, RegisterError, Wake};
+
+const DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const PATH: &str = "/org/freedesktop/portal/desktop";
+const INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+type Results = BTreeMap<String, OwnedValue>;
+
+// what the shortcuts are called on the bus, so `Activated` can tell us which one it was
+fn shortcut_id(event: &HotkeyEvent) -> String {
+    match event {
+        HotkeyEvent::Kill { profile } => format!("profile:{profile}"),
+        HotkeyEvent::Action(action) => format!("action:{action:?}"),
+    }
+}
+
+fn description(event: &HotkeyEvent) -> String {
+    match event {
+        HotkeyEvent::Kill { profile } => format!("Sweep with the {profile} profile"),
+        HotkeyEvent::Action(action) => action.label().to_owned(),
+    }
+}
+
+/// The trigger in the format of the XDG shortcuts spec, e.g. `CTRL+ALT+j`.
+fn trigger(hotkey: &Hotkey) -> String {
+    let mut trigger = String::new();
+    for (held, name) in [
+        (hotkey.ctrl, "CTRL+"),
+        (hotkey.alt, "ALT+"),
+        (hotkey.shift, "SHIFT+"),
+        (hotkey.logo, "LOGO+"),
+    ] {
+        if held {
+            trigger.push_str(name);
+        }
+    }
+    match hotkey.key {
+        Key::Char(c) => trigger.push(c.to_ascii_lowercase()),
+        Key::F(n) => trigger.push_str(&format!("F{n}")),
+    }
+    trigger
+}
+
+fn text(value: &Value<'_>) -> Option<String> {
+    match value {
+        Value::Str(text) => Some(text.to_string()),
+        Value::ObjectPath(path) => Some(path.to_string()),
+        Value::Value(value) => text(value),
+        _ => None,
+    }
+}
+
+// the ids in the `a(sa{sv})` the desktop answers `BindShortcuts` with
+fn bound_ids(results: &Results) -> Vec<String> {
+    let Some(Value::Array(shortcuts)) = results.get("shortcuts").map(|value| &**value) else {
+        return Vec::new();
+    };
+    shortcuts
+        .inner()
+        .iter()
+        .filter_map(|shortcut| match shortcut {
+            Value::Structure(shortcut) => shortcut.fields().first().and_then(text),
+            _ => None,
+        })
+        .collect()
+}
+
+fn failure(e: &str) -> zbus::Error {
+    zbus::Error::Failure(e.to_owned())
+}
+
+/// A connection that's been checked to have the portal on the other end.
+pub struct Portal {
+    proxy: Proxy<'static>,
+    tokens: u32,
+    session: Option<OwnedObjectPath>,
+}
+
+impl Portal {
+    pub fn connect(conn: &Connection) -> zbus::Result<Self> {
+        let proxy = Proxy::new(conn, DESTINATION, PATH, INTERFACE)?;
+        // there's no other way to tell whether the portal has the interface at all
+        let version = proxy.get_property::<u32>("version")?;
+        log::debug!("GlobalShortcuts portal version {version}");
+        Ok(Self {
+            proxy,
+            tokens: 0,
+            session: None,
+        })
+    }
+
+    fn token(&mut self) -> String {
+        self.tokens += 1;
+        format!("expurgate{}_{}", std::process::id(), self.tokens)
+    }
+
+    // every portal call answers with a `Request` object, the actual result comes as its
+    // `Response` signal, so we listen before asking
+    fn request<B>(&self, method: &str, token: &str, body: &B) -> zbus::Result<Results>
+    where
+        B: serde::Serialize + DynamicType,
+    {
+        let conn = self.proxy.connection();
+        let sender = conn
+            .unique_name()
+            .map(|name| name.trim_start_matches(':').replace('.', "_"))
+            .unwrap_or_default();
+        let path = format!("{PATH}/request/{sender}/{token}");
+        let request = Proxy::new(conn, DESTINATION, path, "org.freedesktop.portal.Request")?;
+        let mut responses = request.receive_signal("Response")?;
+
+        self.proxy.call_method(method, body)?;
+
+        let response = responses
+            .next()
+            .ok_or_else(|| failure("the portal went away"))?;
+        let (code, results) = response.body().deserialize::<(u32, Results)>()?;
+        match code {
+            0 => Ok(results),
+            1 => Err(failure("cancelled")),
+            _ => Err(failure("the portal couldn't do it")),
+        }
+    }
+
+    fn close_session(&mut self) {
+        if let Some(session) = self.session.take() {
+            let conn = self.proxy.connection();
+            Proxy::new(conn, DESTINATION, session, "org.freedesktop.portal.Session")
+                .and_then(|session| session.call_method("Close", &()))
+                .ok();
+        }
+    }
+
+    // shortcuts can't be taken back, so every rebind gets a session of its own
+    fn bind(&mut self, bindings: &Bindings) -> zbus::Result<Vec<String>> {
+        self.close_session();
+
+        let (token, session_token) = (self.token(), self.token());
+        let options = BTreeMap::from([
+            ("handle_token", Value::from(token.as_str())),
+            ("session_handle_token", Value::from(session_token.as_str())),
+        ]);
+        let results = self.request("CreateSession", &token, &(options,))?;
+        let session = results
+            .get("session_handle")
+            .and_then(|handle| text(handle))
+            .ok_or_else(|| failure("no session from the portal"))?;
+        let session = OwnedObjectPath::try_from(session)?;
+        self.session = Some(session.clone());
+
+        let shortcuts = bindings
+            .iter()
+            .map(|(hotkey, event)| {
+                let options = BTreeMap::from([
+                    ("description", Value::from(description(event))),
+                    ("preferred_trigger", Value::from(trigger(hotkey))),
+                ]);
+                (shortcut_id(event), options)
+            })
+            .collect::<Vec<_>>();
+        let token = self.token();
+        let options = BTreeMap::from([("handle_token", Value::from(token.as_str()))]);
+        let results = self.request(
+            "BindShortcuts",
+            &token,
+            &(ObjectPath::from(&session), shortcuts, "", options),
+        )?;
+        Ok(bound_ids(&results))
+    }
+
+    /// Starts the threads that bind shortcuts and wait for them to be pressed. What the
+    /// desktop wouldn't bind goes to `answered`, whenever it's done deciding.
+    pub fn listen(self, tx: Sender<HotkeyEvent>, wake: Wake, answered: Answered) -> Listener {
+        let events = Arc::new(Mutex::new(BTreeMap::<String, HotkeyEvent>::new()));
+        let activated = self.proxy.receive_signal("Activated");
+
+        let ids = events.clone();
+        match activated {
+            Ok(activated) => {
+                thread::spawn(move || {
+                    for signal in activated {
+                        let Ok((_, id, _, _)) =
+                            signal
+                                .body()
+                                .deserialize::<(OwnedObjectPath, String, u64, Results)>()
+                        else {
+                            continue;
+                        };
+                        let ids = ids.lock().unwrap_or_else(PoisonError::into_inner);
+                        if let Some(event) = ids.get(&id) {
+                            tx.send(event.clone()).ok();
+                            wake();
+                        }
+                    }
+                });
+            }
+            Err(e) => log::warn!("Can't listen for portal shortcuts: {e}"),
+        }
+
+        let (commands, rx) = mpsc::channel();
+        thread::spawn(move || run(self, &rx, &events, &*answered));
+        Listener { commands }
+    }
+}
+
+fn run(
+    mut portal: Portal,
+    commands: &Receiver<Bindings>,
+    events: &Mutex<BTreeMap<String, HotkeyEvent>>,
+    answered: &dyn Fn(Failures),
+) {
+    for bindings in commands {
+        let failures = match portal.bind(&bindings) {
+            Ok(bound) => {
+                let mut events = events.lock().unwrap_or_else(PoisonError::into_inner);
+                events.clear();
+                let mut failures = Failures::new();
+                for (hotkey, event) in bindings {
+                    let id = shortcut_id(&event);
+                    // the desktop leaves out whatever it wouldn't give us
+                    if bound.contains(&id) {
+                        events.insert(id, event);
+                    } else {
+                        failures.push((hotkey, RegisterError::Taken));
+                    }
+                }
+                failures
+            }
+            Err(e) => bindings
+                .into_iter()
+                .map(|(hotkey, _)| (hotkey, RegisterError::Other(e.to_string())))
+                .collect(),
+        };
+        answered(failures);
+    }
+    portal.close_session();
+}
+
+pub struct Listener {
+    commands: Sender<Bindings>,
+}
+
+impl super::Listener for Listener {
+    // the desktop might be asking the user, that can take as long as they like
+    fn rebind(&self, bindings: Bindings) -> Option<Failures> {
+        let Err(mpsc::SendError(bindings)) = self.commands.send(bindings) else {
+            return None;
+        };
+        let gone = RegisterError::Other("portal thread is gone".to_owned());
+        Some(
+            bindings
+                .into_iter()
+                .map(|(hotkey, _)| (hotkey, gone.clone()))
+                .collect(),
+        )
+    }
+
+    // the desktop picks the keys, there's nothing to probe
+    fn is_free(&self, _hotkey: Hotkey) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead as _, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Arc, mpsc};
+    use std::time::Duration;
+
+    use zbus::blocking::Connection;
+    use zbus::blocking::connection::Builder;
+    use zbus::message::Header;
+    use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+    use super::{INTERFACE, PATH, Portal, trigger};
+    use crate::hotkey::{Action, Hotkey, HotkeyEvent, Listener as _, RegisterError};
+
+    const SESSION: &str = "/org/freedesktop/portal/desktop/session/test";
+
+    // a bus of our own so the tests don't talk to the real portal
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("Failed to spawn dbus-daemon");
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().expect("piped stdout"))
+                .read_line(&mut address)
+                .expect("Failed to read the bus address");
+            Self {
+                daemon,
+                address: address.trim().to_owned(),
+            }
+        }
+
+        fn connect(&self) -> Builder<'static> {
+            Builder::address(self.address.as_str()).expect("Failed to parse the bus address")
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            self.daemon.kill().ok();
+            self.daemon.wait().ok();
+        }
+    }
+
+    // binds whatever it's asked to, except the triggers in `taken`
+    struct MockPortal {
+        taken: Vec<String>,
+    }
+
+    async fn respond(
+        conn: &zbus::Connection,
+        header: &Header<'_>,
+        options: &HashMap<String, OwnedValue>,
+        results: HashMap<&str, Value<'_>>,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        let token = options
+            .get("handle_token")
+            .and_then(|token| super::text(token))
+            .unwrap_or_default();
+        let sender = header
+            .sender()
+            .map(|name| name.trim_start_matches(':').replace('.', "_"))
+            .unwrap_or_default();
+        let path = format!("{PATH}/request/{sender}/{token}");
+        conn.emit_signal(
+            header.sender().cloned(),
+            path.as_str(),
+            "org.freedesktop.portal.Request",
+            "Response",
+            &(0_u32, results),
+        )
+        .await?;
+        Ok(OwnedObjectPath::try_from(path).map_err(zbus::Error::from)?)
+    }
+
+    #[zbus::interface(name = "org.freedesktop.portal.GlobalShortcuts")]
+    impl MockPortal {
+        async fn create_session(
+            &self,
+            #[zbus(header)] header: Header<'_>,
+            #[zbus(connection)] conn: &zbus::Connection,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let results = HashMap::from([("session_handle", Value::from(SESSION))]);
+            respond(conn, &header, &options, results).await
+        }
+
+        async fn bind_shortcuts(
+            &self,
+            #[zbus(header)] header: Header<'_>,
+            #[zbus(connection)] conn: &zbus::Connection,
+            _session: OwnedObjectPath,
+            shortcuts: Vec<(String, HashMap<String, OwnedValue>)>,
+            _parent_window: String,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let bound = shortcuts
+                .into_iter()
+                .filter(|(_, options)| {
+                    let preferred = options
+                        .get("preferred_trigger")
+                        .and_then(|preferred| super::text(preferred))
+                        .unwrap_or_default();
+                    !self.taken.contains(&preferred)
+                })
+                .map(|(id, _)| (id, HashMap::<String, Value<'_>>::new()))
+                .collect::<Vec<_>>();
+            let results = HashMap::from([("shortcuts", Value::from(bound))]);
+            respond(conn, &header, &options, results).await
+        }
+
+        #[zbus(property, name = "version")]
+        #[expect(clippy::unused_self, reason = "properties take self")]
+        fn version(&self) -> u32 {
+            1
+        }
+    }
+
+    fn mock_portal(bus: &Bus, taken: &[Hotkey]) -> Connection {
+        let portal = MockPortal {
+            taken: taken.iter().map(trigger).collect(),
+        };
+        bus.connect()
+            .name(super::DESTINATION)
+            .and_then(|builder| builder.serve_at(PATH, portal))
+            .and_then(Builder::build)
+            .expect("Failed to start the mock portal")
+    }
+
+    #[test]
+    fn triggers_follow_the_shortcuts_spec() {
+        assert_eq!(
+            trigger(&Hotkey::DEFAULT),
+            "CTRL+ALT+j",
+            "letters are keysyms"
+        );
+        let hotkey = "Shift+Super+F5".parse().expect("valid hotkey");
+        assert_eq!(trigger(&hotkey), "SHIFT+LOGO+F5", "super is logo");
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon on PATH"]
+    fn binds_and_activates_through_the_portal() {
+        let bus = Bus::start();
+        let sweep = "Ctrl+Alt+S".parse().expect("valid hotkey");
+        let portal = mock_portal(&bus, &[sweep]);
+
+        let (tx, rx) = mpsc::channel();
+        let (answer, answers) = mpsc::channel();
+        let client = bus.connect().build().expect("Failed to connect");
+        let listener = Portal::connect(&client)
+            .expect("the mock has the portal")
+            .listen(
+                tx,
+                Arc::new(|| {}),
+                Arc::new(move |failures| {
+                    answer.send(failures).ok();
+                }),
+            );
+
+        let default = HotkeyEvent::Kill {
+            profile: "default".to_owned(),
+        };
+        let failures = listener.rebind(vec![
+            (Hotkey::DEFAULT, default.clone()),
+            (sweep, HotkeyEvent::Action(Action::Sweep)),
+        ]);
+        assert_eq!(failures, None, "the desktop answers when it's ready");
+        assert_eq!(
+            answers.recv_timeout(Duration::from_secs(5)),
+            Ok(vec![(sweep, RegisterError::Taken)]),
+            "the desktop kept Ctrl+Alt+S for itself"
+        );
+
+        portal
+            .emit_signal(
+                None::<&str>,
+                PATH,
+                INTERFACE,
+                "Activated",
+                &(
+                    ObjectPath::from_static_str_unchecked(SESSION),
+                    "profile:default",
+                    0_u64,
+                    HashMap::<&str, Value<'_>>::new(),
+                ),
+            )
+            .expect("Failed to activate");
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Ok(default),
+            "pressing it sweeps with the default profile"
+        );
+    }
+}
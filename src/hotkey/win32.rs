@@ -0,0 +1,185 @@
+//! `RegisterHotKey`, on a thread of its own with a message loop.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use windows::Win32::Foundation::{ERROR_HOTKEY_ALREADY_REGISTERED, LPARAM, WPARAM};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, RegisterHotKey,
+    UnregisterHotKey, VK_F1,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetMessageW, MSG, PM_NOREMOVE, PeekMessageW, PostThreadMessageW, WM_APP, WM_HOTKEY,
+};
+use windows::core::HRESULT;
+
+use super::{Bindings, Failures, Hotkey, HotkeyEvent, Key, RegisterError, Wake};
+
+// the highest id an app may use, only ever held for a moment
+const PROBE_ID: i32 = 0xBFFF;
+
+enum Command {
+    Rebind(Bindings, Sender<Failures>),
+    Probe(Hotkey, Sender<bool>),
+}
+
+fn modifiers(hotkey: &Hotkey) -> HOT_KEY_MODIFIERS {
+    let mut modifiers = MOD_NOREPEAT;
+    for (held, modifier) in [
+        (hotkey.ctrl, MOD_CONTROL),
+        (hotkey.alt, MOD_ALT),
+        (hotkey.shift, MOD_SHIFT),
+        (hotkey.logo, MOD_WIN),
+    ] {
+        if held {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+// letters and digits are their own virtual key codes
+fn virtual_key(key: Key) -> u32 {
+    match key {
+        Key::Char(c) => c as u32,
+        Key::F(n) => u32::from(VK_F1.0) + u32::from(n) - 1,
+    }
+}
+
+/// Has to be called on the thread that'll get the `WM_HOTKEY`.
+#[allow(unsafe_code)]
+fn register_hotkey(id: i32, hotkey: &Hotkey) -> Result<(), RegisterError> {
+    unsafe { RegisterHotKey(None, id, modifiers(hotkey), virtual_key(hotkey.key)) }.map_err(|e| {
+        if e.code() == HRESULT::from_win32(ERROR_HOTKEY_ALREADY_REGISTERED.0) {
+            RegisterError::Taken
+        } else {
+            RegisterError::Other(e.to_string())
+        }
+    })
+}
+
+#[allow(unsafe_code)]
+fn unregister_hotkey(id: i32) {
+    // fails if it never got registered, which is fine
+    unsafe { UnregisterHotKey(None, id) }.ok();
+}
+
+// ids are the index + 1, 0 isn't a valid one
+fn rebind(old: &Bindings, new: &Bindings) -> Failures {
+    for id in (1..).take(old.len()) {
+        unregister_hotkey(id);
+    }
+    (1..)
+        .zip(new)
+        .filter_map(|(id, (hotkey, _))| register_hotkey(id, hotkey).err().map(|e| (*hotkey, e)))
+        .collect()
+}
+
+fn handle(command: Command, bindings: &mut Bindings) {
+    match command {
+        Command::Rebind(new, reply) => {
+            reply.send(rebind(bindings, &new)).ok();
+            *bindings = new;
+        }
+        Command::Probe(hotkey, reply) => {
+            let free = register_hotkey(PROBE_ID, &hotkey).is_ok();
+            if free {
+                unregister_hotkey(PROBE_ID);
+            }
+            reply.send(free).ok();
+        }
+    }
+}
+
+pub struct Listener {
+    thread_id: u32,
+    commands: Sender<Command>,
+}
+
+impl Listener {
+    // the thread only looks at commands when its message loop wakes up
+    #[allow(unsafe_code)]
+    fn ask<T>(&self, command: impl FnOnce(Sender<T>) -> Command) -> Option<T> {
+        let (tx, rx) = mpsc::channel();
+        self.commands.send(command(tx)).ok()?;
+        unsafe { PostThreadMessageW(self.thread_id, WM_APP, WPARAM(0), LPARAM(0)) }.ok()?;
+        rx.recv_timeout(Duration::from_secs(1)).ok()
+    }
+}
+
+impl super::Listener for Listener {
+    fn rebind(&self, bindings: Bindings) -> Option<Failures> {
+        let hotkeys = bindings
+            .iter()
+            .map(|(hotkey, _)| *hotkey)
+            .collect::<Vec<_>>();
+        let failures = self
+            .ask(|reply| Command::Rebind(bindings, reply))
+            .unwrap_or_else(|| {
+                hotkeys
+                    .into_iter()
+                    .map(|hotkey| {
+                        (
+                            hotkey,
+                            RegisterError::Other("hotkey thread is gone".to_owned()),
+                        )
+                    })
+                    .collect()
+            });
+        Some(failures)
+    }
+
+    fn is_free(&self, hotkey: Hotkey) -> bool {
+        self.ask(|reply| Command::Probe(hotkey, reply))
+            .unwrap_or(false)
+    }
+}
+
+#[allow(unsafe_code)]
+fn run(commands: &Receiver<Command>, tx: &Sender<HotkeyEvent>, wake: &Wake) {
+    let mut bindings = Bindings::new();
+    let mut msg = MSG::default();
+    unsafe {
+        while GetMessageW(&mut msg, None, 0, 0).into() {
+            match msg.message {
+                WM_APP => {
+                    while let Ok(command) = commands.try_recv() {
+                        handle(command, &mut bindings);
+                    }
+                }
+                WM_HOTKEY => {
+                    if let Some((_, event)) =
+                        msg.wParam.0.checked_sub(1).and_then(|i| bindings.get(i))
+                    {
+                        tx.send(event.clone()).ok();
+                        wake();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// handling closing with a hotkey
+#[allow(unsafe_code)]
+pub fn start_hotkey_listener(tx: Sender<HotkeyEvent>, wake: Wake) -> Listener {
+    let (commands_tx, commands) = mpsc::channel();
+    let (id_tx, id_rx) = mpsc::channel();
+    thread::spawn(move || {
+        // makes sure the thread has a message queue before anyone posts to it
+        let mut msg = MSG::default();
+        unsafe {
+            PeekMessageW(&mut msg, None, 0, 0, PM_NOREMOVE).ok();
+            id_tx.send(GetCurrentThreadId()).ok();
+        }
+        run(&commands, &tx, &wake);
+    });
+
+    Listener {
+        thread_id: id_rx.recv().unwrap_or_default(),
+        commands: commands_tx,
+    }
+}
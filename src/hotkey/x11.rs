@@ -0,0 +1,272 @@
+//! Hotkeys on X11, grabbed on the root window so they work whatever has focus.
+
+use std::io;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+use x11rb::connection::Connection as _;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, Keycode, Keysym, ModMask, Window};
+use x11rb::protocol::{ErrorKind, Event};
+use x11rb::rust_connection::RustConnection;
+
+use super::{Bindings, Failures, Hotkey, HotkeyEvent, Key, RegisterError, Wake};
+
+// shift, control, mod1 (alt) and mod4 (super), numlock (mod2) and capslock are left out
+const MODIFIERS: u16 = 1 | (1 << 2) | (1 << 3) | (1 << 6);
+
+const XK_F1: Keysym = 0xffbe;
+
+fn modifiers(hotkey: &Hotkey) -> ModMask {
+    let mut modifiers = ModMask::from(0u8);
+    for (held, modifier) in [
+        (hotkey.ctrl, ModMask::CONTROL),
+        (hotkey.alt, ModMask::M1),
+        (hotkey.shift, ModMask::SHIFT),
+        (hotkey.logo, ModMask::M4),
+    ] {
+        if held {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+// grabs are exact, so numlock or capslock being on needs a grab of its own
+fn with_locks(modifiers: ModMask) -> [ModMask; 4] {
+    [
+        modifiers,
+        modifiers | ModMask::LOCK,
+        modifiers | ModMask::M2,
+        modifiers | ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+// latin-1 keysyms are the characters themselves, letters are looked up lowercase
+fn keysym(key: Key) -> Keysym {
+    match key {
+        Key::Char(c) => c.to_ascii_lowercase().into(),
+        Key::F(n) => XK_F1 + Keysym::from(n) - 1,
+    }
+}
+
+struct Grab {
+    keycode: Keycode,
+    modifiers: ModMask,
+    event: HotkeyEvent,
+}
+
+pub struct Listener {
+    conn: Arc<RustConnection>,
+    root: Window,
+    grabs: Arc<Mutex<Vec<Grab>>>,
+}
+
+impl Listener {
+    /// Connects to `display`, or `$DISPLAY` if that's `None`.
+    pub fn connect(display: Option<&str>) -> io::Result<Self> {
+        let (conn, screen) = x11rb::connect(display).map_err(io::Error::other)?;
+        let root = conn.setup().roots[screen].root;
+        Ok(Self {
+            conn: Arc::new(conn),
+            root,
+            grabs: Arc::default(),
+        })
+    }
+
+    /// Starts the thread that waits for the grabbed keys.
+    pub fn listen(self, tx: Sender<HotkeyEvent>, wake: Wake) -> Self {
+        let conn = self.conn.clone();
+        let grabs = self.grabs.clone();
+        thread::spawn(move || {
+            while let Ok(event) = conn.wait_for_event() {
+                let Event::KeyPress(press) = event else {
+                    continue;
+                };
+                let modifiers = u16::from(press.state) & MODIFIERS;
+                let grabs = grabs.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(grab) = grabs.iter().find(|grab| {
+                    grab.keycode == press.detail && u16::from(grab.modifiers) == modifiers
+                }) {
+                    tx.send(grab.event.clone()).ok();
+                    wake();
+                }
+            }
+        });
+        self
+    }
+
+    fn keycode(&self, key: Key) -> Option<Keycode> {
+        let setup = self.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(min, max - min + 1)
+            .ok()?
+            .reply()
+            .ok()?;
+        let keysym = keysym(key);
+        mapping
+            .keysyms
+            .chunks(usize::from(mapping.keysyms_per_keycode).max(1))
+            .position(|keysyms| keysyms.contains(&keysym))
+            .and_then(|i| u8::try_from(i).ok())
+            .map(|i| min + i)
+    }
+
+    fn ungrab(&self, keycode: Keycode, modifiers: ModMask) {
+        for modifiers in with_locks(modifiers) {
+            self.conn.ungrab_key(keycode, self.root, modifiers).ok();
+        }
+    }
+
+    fn grab(&self, hotkey: &Hotkey) -> Result<(Keycode, ModMask), RegisterError> {
+        let keycode = self
+            .keycode(hotkey.key)
+            .ok_or_else(|| RegisterError::Other(format!("no key for {hotkey} on this keyboard")))?;
+        let modifiers = modifiers(hotkey);
+        for locked in with_locks(modifiers) {
+            let grabbed = self
+                .conn
+                .grab_key(
+                    true,
+                    self.root,
+                    locked,
+                    keycode,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                )
+                .map_err(ReplyError::from)
+                .and_then(|cookie| cookie.check());
+            if let Err(e) = grabbed {
+                self.ungrab(keycode, modifiers);
+                return Err(match e {
+                    ReplyError::X11Error(e) if e.error_kind == ErrorKind::Access => {
+                        RegisterError::Taken
+                    }
+                    e => RegisterError::Other(e.to_string()),
+                });
+            }
+        }
+        Ok((keycode, modifiers))
+    }
+}
+
+impl super::Listener for Listener {
+    fn rebind(&self, bindings: Bindings) -> Option<Failures> {
+        let mut grabs = self.grabs.lock().unwrap_or_else(PoisonError::into_inner);
+        for grab in grabs.drain(..) {
+            self.ungrab(grab.keycode, grab.modifiers);
+        }
+
+        let mut failures = Failures::new();
+        for (hotkey, event) in bindings {
+            match self.grab(&hotkey) {
+                Ok((keycode, modifiers)) => grabs.push(Grab {
+                    keycode,
+                    modifiers,
+                    event,
+                }),
+                Err(e) => failures.push((hotkey, e)),
+            }
+        }
+        self.conn.flush().ok();
+        Some(failures)
+    }
+
+    fn is_free(&self, hotkey: Hotkey) -> bool {
+        // probing one of ours would let go of it, and nobody else can have it anyway
+        let ours = self.keycode(hotkey.key).is_some_and(|keycode| {
+            let modifiers = modifiers(&hotkey);
+            let grabs = self.grabs.lock().unwrap_or_else(PoisonError::into_inner);
+            grabs
+                .iter()
+                .any(|grab| grab.keycode == keycode && grab.modifiers == modifiers)
+        });
+        if ours {
+            return true;
+        }
+        let free = self
+            .grab(&hotkey)
+            .map(|(keycode, modifiers)| self.ungrab(keycode, modifiers))
+            .is_ok();
+        self.conn.flush().ok();
+        free
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::{Child, Command};
+    use std::sync::Arc;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::Listener;
+    use crate::hotkey::{Hotkey, HotkeyEvent, Listener as _, RegisterError};
+
+    struct Xvfb(Child);
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            self.0.kill().ok();
+            self.0.wait().ok();
+        }
+    }
+
+    fn xvfb(number: u32) -> Xvfb {
+        let server = Command::new("Xvfb")
+            .args([&format!(":{number}"), "-nolisten", "tcp"])
+            .spawn()
+            .expect("Failed to spawn Xvfb");
+        let started = Instant::now();
+        while !Path::new(&format!("/tmp/.X11-unix/X{number}")).exists() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "Xvfb didn't come up"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+        Xvfb(server)
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb on PATH"]
+    fn someone_elses_grab_is_taken() {
+        let _xvfb = xvfb(90);
+        let (tx, _rx) = mpsc::channel();
+        let first = Listener::connect(Some(":90"))
+            .expect("Failed to connect")
+            .listen(tx.clone(), Arc::new(|| {}));
+        let second = Listener::connect(Some(":90"))
+            .expect("Failed to connect")
+            .listen(tx, Arc::new(|| {}));
+
+        let sweep = HotkeyEvent::Kill {
+            profile: "default".to_owned(),
+        };
+        assert_eq!(
+            first.rebind(vec![(Hotkey::DEFAULT, sweep.clone())]),
+            Some(Vec::new()),
+            "nobody has it yet"
+        );
+        assert_eq!(
+            second.rebind(vec![(Hotkey::DEFAULT, sweep)]),
+            Some(vec![(Hotkey::DEFAULT, RegisterError::Taken)]),
+            "the first one has it"
+        );
+        assert!(!second.is_free(Hotkey::DEFAULT), "still taken");
+        assert!(first.is_free(Hotkey::DEFAULT), "ours is fine for us");
+        assert!(
+            !second.is_free(Hotkey::DEFAULT),
+            "and asking didn't let go of it"
+        );
+
+        let other = "Ctrl+Shift+J".parse().expect("valid hotkey");
+        assert!(second.is_free(other), "different modifiers are free");
+        assert!(first.is_free(other), "and probing doesn't keep it");
+    }
+}
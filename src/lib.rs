@@ -1,4 +1,23 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+pub mod backend;
+pub mod classifier;
+pub mod cli;
+pub mod close;
+pub mod config;
+pub mod countdown;
+pub mod dry_run;
+pub mod hotkey;
+pub mod policy;
+pub mod processes;
+pub mod profiles;
+pub mod rules;
+pub mod ruleset;
+pub mod schedule;
+pub mod scheduler;
+pub mod session;
+mod tray;
+pub mod watch;
+pub mod worker;
 pub use app::Expurgate;
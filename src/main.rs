@@ -1,15 +1,29 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use std::sync::mpsc;
-use std::thread;
-use tray_item::{IconSource, TrayItem};
-
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
-fn main() -> eframe::Result {
+fn main() -> std::process::ExitCode {
+    use clap::Parser as _;
+
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    // a subcommand runs without a window, e.g. `expurgate sweep` from a script
+    if let Some(command) = expurgate::cli::Cli::parse().command {
+        return expurgate::cli::run(command);
+    }
+
+    match run_window() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("Failed to run the window: {e}");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_window() -> eframe::Result {
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             // note, tied this one to unhide in app.rs
@@ -18,8 +32,10 @@ fn main() -> eframe::Result {
             .with_min_inner_size([350.0, 480.0])
             .with_icon(
                 // NOTE: Adding an icon is optional
-                eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon-256-temp.png")[..])
-                    .expect("Failed to load icon"),
+                eframe::icon_data::from_png_bytes(
+                    &include_bytes!("../assets/icon-256-temp.png")[..],
+                )
+                .expect("Failed to load icon"),
             ),
         ..Default::default()
     };
@@ -0,0 +1,275 @@
+//! Rules forced on top of the user's config, for shared machines.
+//!
+//! An admin's policy comes from a system-wide file, and overrides for just this login
+//! session come from the runtime directory. Both are rule sets, put into every profile
+//! whenever it changes and undone again before the config is written, so they can't be
+//! removed and never end up in the user's file, nor take the user's own copy of a rule out
+//! of it. The policy beats the session, which beats the user.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::profiles::{Profile, Profiles};
+use crate::rules::Rule;
+use crate::ruleset::{List, RuleSet};
+
+/// Which layer a locked rule is from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Policy,
+    Session,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Policy => "the system policy",
+            Self::Session => "this session's overrides",
+        }
+    }
+}
+
+/// Where a locked rule has to be.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lock {
+    pub list: List,
+    pub source: Source,
+    pub comment: String,
+}
+
+/// How the user had a rule before it was locked.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Displaced {
+    allowlist: bool,
+    killlist: bool,
+    comment: Option<String>,
+}
+
+/// The rules nobody gets to take out, by where they go.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layers {
+    locked: BTreeMap<Rule, Lock>,
+}
+
+// not from an environment variable, anyone could point that at an empty file
+#[cfg(windows)]
+fn policy_path() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map_or_else(|| PathBuf::from(r"C:\ProgramData"), PathBuf::from)
+        .join("expurgate")
+        .join("policy.toml")
+}
+
+#[cfg(not(windows))]
+fn policy_path() -> PathBuf {
+    PathBuf::from("/etc/expurgate/policy.toml")
+}
+
+// the runtime dir goes away when the session ends, the variable is for launchers (and windows)
+fn session_path() -> Option<PathBuf> {
+    std::env::var_os("EXPURGATE_SESSION")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let dir = PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?);
+            Some(dir.join("expurgate").join("session.toml"))
+        })
+}
+
+// nothing there is fine, most machines don't have either
+fn read(path: Option<PathBuf>) -> RuleSet {
+    let Some(path) = path.filter(|path| path.exists()) else {
+        return RuleSet::default();
+    };
+    RuleSet::load(&path)
+        .inspect(|_| log::info!("Locked rules from {}", path.display()))
+        .inspect_err(|e| log::error!("Failed to read {}: {e}", path.display()))
+        .unwrap_or_default()
+}
+
+impl Layers {
+    pub fn new(policy: &RuleSet, session: &RuleSet) -> Self {
+        let mut locked = BTreeMap::new();
+        // the policy goes in last, so it's the one that stays
+        for (source, set) in [(Source::Session, session), (Source::Policy, policy)] {
+            let allow = set.allowlist.iter().map(|entry| (List::Allow, entry));
+            let kill = set.killlist.iter().map(|entry| (List::Kill, entry));
+            for (list, entry) in allow.chain(kill) {
+                let lock = Lock {
+                    list,
+                    source,
+                    comment: entry.comment.clone(),
+                };
+                locked.insert(entry.rule.clone(), lock);
+            }
+        }
+        Self { locked }
+    }
+
+    /// The policy and this session's overrides, from wherever they are on this machine.
+    pub fn load() -> Self {
+        Self::new(&read(Some(policy_path())), &read(session_path()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locked.is_empty()
+    }
+
+    pub fn lock(&self, rule: &Rule) -> Option<&Lock> {
+        self.locked.get(rule)
+    }
+
+    fn enforce_one(&self, profile: &mut Profile) {
+        for (rule, lock) in &self.locked {
+            let (ours, other) = match lock.list {
+                List::Allow => (&profile.allowlist, &profile.killlist),
+                List::Kill => (&profile.killlist, &profile.allowlist),
+            };
+            // looking first, this happens every frame
+            let moved = !ours.contains(rule) || other.contains(rule);
+            let recomment = !lock.comment.is_empty() && profile.comment(rule) != lock.comment;
+            if !moved && !recomment {
+                continue;
+            }
+            // only what changes now is the user's, the rest may be ours from last time
+            let allowlist = profile.allowlist.contains(rule);
+            let killlist = profile.killlist.contains(rule);
+            let comment = profile.comments.get(rule).cloned();
+            let was = profile
+                .displaced
+                .entry(rule.clone())
+                .or_insert_with(|| Displaced {
+                    allowlist,
+                    killlist,
+                    comment: comment.clone(),
+                });
+            if moved {
+                was.allowlist = allowlist;
+                was.killlist = killlist;
+                let (ours, other) = match lock.list {
+                    List::Allow => (&mut profile.allowlist, &mut profile.killlist),
+                    List::Kill => (&mut profile.killlist, &mut profile.allowlist),
+                };
+                ours.insert(rule.clone());
+                other.remove(rule);
+            }
+            if recomment {
+                was.comment = comment;
+                profile.comments.insert(rule.clone(), lock.comment.clone());
+            }
+        }
+    }
+
+    /// Puts every locked rule on its list in every profile, off the other one.
+    pub fn enforce(&self, profiles: &mut Profiles) {
+        for profile in profiles.all_mut() {
+            self.enforce_one(profile);
+        }
+    }
+
+    /// Puts back what the locked rules changed, for what goes in the user's file. Rules the
+    /// user already had where they're locked stay, comment and all.
+    pub fn strip(&self, profiles: &mut Profiles) {
+        for profile in profiles.all_mut() {
+            for (rule, was) in std::mem::take(&mut profile.displaced) {
+                for (list, keep) in [
+                    (&mut profile.allowlist, was.allowlist),
+                    (&mut profile.killlist, was.killlist),
+                ] {
+                    if keep {
+                        list.insert(rule.clone());
+                    } else {
+                        list.remove(&rule);
+                    }
+                }
+                match was.comment {
+                    Some(comment) => profile.comments.insert(rule, comment),
+                    None => profile.comments.remove(&rule),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layers, Source};
+    use crate::profiles::{DEFAULT_PROFILE, Profiles};
+    use crate::rules::Rule;
+    use crate::ruleset::RuleSet;
+
+    #[test]
+    fn the_policy_beats_the_session_beats_the_user() {
+        let policy: RuleSet = r#"
+            allowlist = [{ rule = "exam-client", comment = "needed for exams" }]
+            killlist = [{ rule = "steam.exe" }]
+        "#
+        .parse()
+        .expect("a valid policy");
+        let session: RuleSet = r#"
+            allowlist = [{ rule = "steam.exe" }]
+            killlist = [{ rule = "exam-client" }, { rule = "discord.exe" }]
+        "#
+        .parse()
+        .expect("valid overrides");
+        let layers = Layers::new(&policy, &session);
+
+        let mut profiles = Profiles::default();
+        let profile = profiles
+            .get_mut(DEFAULT_PROFILE)
+            .expect("there's a default profile");
+        profile.killlist.insert(Rule::name("exam-client"));
+        profile.allowlist.insert(Rule::name("discord.exe"));
+        profile.allowlist.insert(Rule::name("code.exe"));
+        // the user's own, where the policy wants it anyway
+        profile.killlist.insert(Rule::name("steam.exe"));
+        profile
+            .comments
+            .insert(Rule::name("steam.exe"), "the kids' games".to_owned());
+        profile
+            .comments
+            .insert(Rule::name("exam-client"), "cheating".to_owned());
+        profiles.add("lab");
+        let theirs = profiles.clone();
+
+        layers.enforce(&mut profiles);
+        for name in [DEFAULT_PROFILE, "lab"] {
+            let profile = profiles.get(name).expect("there's the profile");
+            assert!(
+                profile.allowlist.contains(&Rule::name("exam-client"))
+                    && !profile.killlist.contains(&Rule::name("exam-client")),
+                "{name}: the policy's allowed"
+            );
+            assert!(
+                profile.killlist.contains(&Rule::name("discord.exe"))
+                    && !profile.allowlist.contains(&Rule::name("discord.exe")),
+                "{name}: the session's killed"
+            );
+            assert!(
+                profile.killlist.contains(&Rule::name("steam.exe")),
+                "{name}: the policy over the session"
+            );
+            assert_eq!(
+                profile.comment(&Rule::name("exam-client")),
+                "needed for exams",
+                "{name}: with the admin's comment"
+            );
+        }
+        assert_eq!(
+            layers
+                .lock(&Rule::name("discord.exe"))
+                .map(|lock| lock.source),
+            Some(Source::Session),
+            "knows where it's from"
+        );
+
+        let before = profiles.clone();
+        layers.enforce(&mut profiles);
+        assert_eq!(profiles, before, "nothing more the second time");
+
+        layers.strip(&mut profiles);
+        assert_eq!(
+            profiles, theirs,
+            "the user's file gets their own rules back, as they had them"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use sysinfo::System;
+
+use crate::backend::{WindowBackend, WindowId};
+
+/// What we know about a process apart from its windows, for the rules to look at.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub name: String,
+    /// Full path of the executable, if we're allowed to see it.
+    pub exe: Option<PathBuf>,
+    /// Command line arguments, without the executable.
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// Seconds since the epoch, `None` if it's not from sysinfo (the mock backend).
+    pub start_time: Option<u64>,
+}
+
+impl ProcessInfo {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Every process sysinfo knows about by pid, it needs to be refreshed with exe and cmd.
+pub fn process_infos(sys: &System) -> BTreeMap<u32, ProcessInfo> {
+    sys.processes()
+        .iter()
+        .map(|(pid, process)| {
+            let info = ProcessInfo {
+                name: process.name().to_string_lossy().into_owned(),
+                exe: process.exe().map(PathBuf::from),
+                args: process
+                    .cmd()
+                    .iter()
+                    .skip(1)
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect(),
+                cwd: process.cwd().map(PathBuf::from),
+                start_time: Some(process.start_time()),
+            };
+            (pid.as_u32(), info)
+        })
+        .collect()
+}
+
+/// A top-level window of some process, with what we need to decide whether to close it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackedWindow {
+    pub id: WindowId,
+    /// Only fetched for visible windows, empty otherwise.
+    pub title: String,
+    /// Same as the title, only for visible windows.
+    pub class: String,
+    pub visible: bool,
+    /// Visible and (pseudo) in the taskbar, i.e. a window the user actually sees.
+    pub in_taskbar: bool,
+}
+
+/// One running process and all of its top-level windows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackedProcess {
+    pub info: ProcessInfo,
+    pub windows: Vec<TrackedWindow>,
+}
+
+/// Every process of one executable by pid.
+pub type Processes = BTreeMap<u32, TrackedProcess>;
+
+/// Open executables by process name, e.g. three notepad.exe end up under one key.
+pub type ProcessList = BTreeMap<String, Processes>;
+
+/// Processes to close with their name and pid, in the order they get asked.
+pub type ToClose = Vec<(String, u32, TrackedProcess)>;
+
+/// Everything we want to know about one window, with the pid it belongs to.
+pub fn track_window(backend: &dyn WindowBackend, id: WindowId) -> Option<(u32, TrackedWindow)> {
+    let pid = backend.owner_pid(id)?;
+    let visible = backend.is_visible(id);
+    let in_taskbar = visible && backend.is_in_taskbar(id);
+    let (title, class) = if visible {
+        (backend.title(id), backend.class(id))
+    } else {
+        (String::new(), String::new())
+    };
+    let window = TrackedWindow {
+        id,
+        title,
+        class,
+        visible,
+        in_taskbar,
+    };
+    Some((pid, window))
+}
+
+/// Adds a window that just opened to its process, false if the process isn't in `list`
+/// (it's new, or had no windows so far).
+pub fn add_window(list: &mut ProcessList, pid: u32, window: TrackedWindow) -> bool {
+    let Some(process) = list
+        .values_mut()
+        .find_map(|processes| processes.get_mut(&pid))
+    else {
+        return false;
+    };
+    if let Some(known) = process
+        .windows
+        .iter_mut()
+        .find(|known| known.id == window.id)
+    {
+        *known = window;
+    } else {
+        process.windows.push(window);
+    }
+    true
+}
+
+/// Takes a window that closed out of `list`, along with its process if that was its last
+/// one. False if it wasn't in there.
+pub fn forget_window(list: &mut ProcessList, id: WindowId) -> bool {
+    let mut found = false;
+    for processes in list.values_mut() {
+        for process in processes.values_mut() {
+            let before = process.windows.len();
+            process.windows.retain(|window| window.id != id);
+            found |= process.windows.len() != before;
+        }
+        processes.retain(|_, process| !process.windows.is_empty());
+    }
+    list.retain(|_, processes| !processes.is_empty());
+    found
+}
+
+/// Processes with a window that (pseudo) shows up in the taskbar, grouped by process name.
+pub fn collect_processes(
+    backend: &dyn WindowBackend,
+    processes: &BTreeMap<u32, ProcessInfo>,
+    show_all_processes: bool,
+) -> ProcessList {
+    // one pass over the windows instead of one per process
+    let mut windows: BTreeMap<u32, Vec<TrackedWindow>> = BTreeMap::new();
+    for id in backend.top_level_windows() {
+        if let Some((pid, window)) = track_window(backend, id) {
+            windows.entry(pid).or_default().push(window);
+        }
+    }
+
+    let mut list = ProcessList::new();
+    for (pid, windows) in windows {
+        let Some(info) = processes.get(&pid) else {
+            continue;
+        };
+        if show_all_processes || windows.iter().any(|window| window.in_taskbar) {
+            // we don't strip file extension at the source because we will use in the actual allowlist,
+            // so it's removed only in display
+            list.entry(info.name.clone()).or_default().insert(
+                pid,
+                TrackedProcess {
+                    info: info.clone(),
+                    windows,
+                },
+            );
+        }
+    }
+    list
+}
+
+/// The windows closing a process should go for: the ones the user sees in the taskbar.
+///
+/// Hidden helper windows only get a `WM_CLOSE` if there's nothing better, which only happens
+/// for stuff that's explicitly on the killlist.
+pub fn close_targets(windows: &[TrackedWindow]) -> Vec<WindowId> {
+    let pick = |keep: fn(&TrackedWindow) -> bool| -> Vec<WindowId> {
+        windows
+            .iter()
+            .filter(|window| keep(window))
+            .map(|window| window.id)
+            .collect()
+    };
+
+    let targets = pick(|window| window.in_taskbar);
+    if !targets.is_empty() {
+        return targets;
+    }
+    let targets = pick(|window| window.visible);
+    if !targets.is_empty() {
+        return targets;
+    }
+    pick(|_| true)
+}
@@ -0,0 +1,213 @@
+//! Named sets of lists, e.g. "work" keeps the IDE open and "end of day" closes everything.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::close::CloseStrategy;
+use crate::hotkey::{Hotkey, HotkeyEvent};
+use crate::policy::Displaced;
+use crate::rules::Rule;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Profile {
+    pub allowlist: BTreeSet<Rule>,
+    pub killlist: BTreeSet<Rule>,
+    pub close_strategy: CloseStrategy,
+    pub strategy_overrides: BTreeMap<String, CloseStrategy>,
+    /// Sweeps with this profile whichever one is active.
+    pub hotkey: Option<Hotkey>,
+    /// Seconds to show what a hotkey sweep is about to close first, 0 sweeps right away.
+    pub countdown_secs: u64,
+    /// Why a rule is on either list, if anyone said. Comes and goes with shared rule sets.
+    pub comments: BTreeMap<Rule, String>,
+    /// What the locked rules pushed aside, put back before the config is written.
+    #[serde(skip)]
+    pub displaced: BTreeMap<Rule, Displaced>,
+}
+
+impl Profile {
+    pub fn comment(&self, rule: &Rule) -> &str {
+        self.comments.get(rule).map_or("", String::as_str)
+    }
+
+    pub fn strategy_for(&self, name: &str) -> &CloseStrategy {
+        self.strategy_overrides
+            .get(name)
+            .unwrap_or(&self.close_strategy)
+    }
+}
+
+/// Every profile by name and which one the ui and the "Kill them all." button use.
+/// There's always at least one.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Profiles {
+    active: String,
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        let profile = Profile {
+            hotkey: Some(Hotkey::DEFAULT),
+            ..Profile::default()
+        };
+        Self {
+            active: DEFAULT_PROFILE.to_owned(),
+            profiles: BTreeMap::from([(DEFAULT_PROFILE.to_owned(), profile)]),
+        }
+    }
+}
+
+impl Profiles {
+    pub fn active_name(&self) -> &str {
+        &self.active
+    }
+
+    pub fn active(&self) -> &Profile {
+        self.profiles
+            .get(&self.active)
+            .expect("the active profile always exists")
+    }
+
+    pub fn active_mut(&mut self) -> &mut Profile {
+        self.profiles
+            .get_mut(&self.active)
+            .expect("the active profile always exists")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        self.profiles.get_mut(name)
+    }
+
+    pub fn all_mut(&mut self) -> impl Iterator<Item = &mut Profile> {
+        self.profiles.values_mut()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Returns false if there's no such profile.
+    pub fn switch(&mut self, name: &str) -> bool {
+        if !self.profiles.contains_key(name) {
+            return false;
+        }
+        name.clone_into(&mut self.active);
+        true
+    }
+
+    /// A copy of the active profile under a new name, without its hotkey. Switches to it.
+    pub fn add(&mut self, name: &str) -> bool {
+        if name.is_empty() || self.profiles.contains_key(name) {
+            return false;
+        }
+        let profile = Profile {
+            hotkey: None,
+            ..self.active().clone()
+        };
+        self.profiles.insert(name.to_owned(), profile);
+        self.switch(name)
+    }
+
+    /// Removes the active profile, unless it's the last one.
+    pub fn remove_active(&mut self) {
+        if self.profiles.len() < 2 {
+            return;
+        }
+        self.profiles.remove(&self.active);
+        if let Some(name) = self.profiles.keys().next() {
+            self.active = name.clone();
+        }
+    }
+
+    /// What to register with the hotkey listener.
+    pub fn hotkey_bindings(&self) -> Vec<(Hotkey, HotkeyEvent)> {
+        self.profiles
+            .iter()
+            .filter_map(|(name, profile)| {
+                let event = HotkeyEvent::Kill {
+                    profile: name.clone(),
+                };
+                profile.hotkey.map(|hotkey| (hotkey, event))
+            })
+            .collect()
+    }
+
+    // loading might have left us without anything to be active
+    pub fn repair(&mut self) {
+        if self.profiles.is_empty() {
+            *self = Self::default();
+        } else if !self.profiles.contains_key(&self.active) {
+            if let Some(name) = self.profiles.keys().next() {
+                self.active = name.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_PROFILE, Profiles};
+    use crate::hotkey::{Hotkey, HotkeyEvent};
+    use crate::rules::Rule;
+
+    #[test]
+    fn new_profiles_start_as_a_copy() {
+        let mut profiles = Profiles::default();
+        profiles
+            .active_mut()
+            .allowlist
+            .insert(Rule::name("code.exe"));
+
+        assert!(profiles.add("work"), "new name");
+        assert!(!profiles.add("work"), "taken name");
+        assert_eq!(profiles.active_name(), "work", "switched to it");
+        assert_eq!(
+            profiles.active().allowlist.len(),
+            1,
+            "with the lists it was made from"
+        );
+        assert_eq!(profiles.active().hotkey, None, "but not the hotkey");
+    }
+
+    #[test]
+    fn the_last_profile_stays() {
+        let mut profiles = Profiles::default();
+        profiles.remove_active();
+        assert_eq!(
+            profiles.names().collect::<Vec<_>>(),
+            [DEFAULT_PROFILE],
+            "can't remove the only one"
+        );
+
+        profiles.add("stream");
+        profiles.remove_active();
+        assert_eq!(profiles.active_name(), DEFAULT_PROFILE, "back to default");
+    }
+
+    #[test]
+    fn hotkeys_carry_their_profile() {
+        let mut profiles = Profiles::default();
+        profiles.add("end of day");
+        profiles.active_mut().hotkey = "Ctrl+Alt+E".parse().ok();
+
+        let bindings = profiles.hotkey_bindings();
+        assert_eq!(bindings.len(), 2, "both have a hotkey");
+        assert!(
+            bindings.contains(&(
+                Hotkey::DEFAULT,
+                HotkeyEvent::Kill {
+                    profile: DEFAULT_PROFILE.to_owned()
+                }
+            )),
+            "the default one keeps Ctrl+Alt+J"
+        );
+    }
+}
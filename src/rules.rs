@@ -0,0 +1,304 @@
+//! Allowlist and killlist entries. A plain entry is a process name like before, everything
+//! else is `field=glob` or `field~=regex`, e.g. `path=/opt/jetbrains/**` or
+//! `title~=".*- Visual Studio Code$"`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use globset::GlobBuilder;
+use regex::bytes::Regex;
+
+use crate::processes::TrackedProcess;
+
+/// What a rule looks at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// The process name, e.g. `code.exe`.
+    Name,
+    /// The full path of the executable, with `/` as the separator everywhere.
+    Path,
+    /// The title of any visible window.
+    Title,
+    /// The class of any visible window (`app_id` on Wayland).
+    Class,
+    /// The command line arguments, joined by spaces.
+    Args,
+}
+
+impl Field {
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "name" => Some(Self::Name),
+            "path" => Some(Self::Path),
+            "title" => Some(Self::Title),
+            "class" | "app_id" => Some(Self::Class),
+            "args" | "cmdline" => Some(Self::Args),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleError(String);
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// One allowlist/killlist entry. Stored as what the user typed, so old lists of plain
+/// names load just fine.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rule {
+    source: String,
+    field: Field,
+    /// Globs end up as a regex too, over bytes since that's what globset gives us.
+    pattern: Regex,
+}
+
+impl Rule {
+    /// Exactly this process name, what the "+" buttons add.
+    pub fn name(name: &str) -> Self {
+        let escaped = globset::escape(name);
+        let source = if escaped == name {
+            name.to_owned()
+        } else {
+            format!("name={escaped}")
+        };
+        source
+            .parse()
+            .expect("an escaped process name is always a valid glob")
+    }
+
+    /// Whether it's just a process name, those are shown without the extension.
+    pub fn is_plain(&self) -> bool {
+        self.field == Field::Name && !self.source.contains('=')
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, process: &TrackedProcess) -> bool {
+        let info = &process.info;
+        let is_match = |text: &str| self.pattern.is_match(text.as_bytes());
+        let mut windows = process.windows.iter().filter(|window| window.visible);
+        match self.field {
+            Field::Name => is_match(&info.name),
+            Field::Path => info
+                .exe
+                .as_deref()
+                .is_some_and(|exe| is_match(&exe.to_string_lossy().replace('\\', "/"))),
+            Field::Title => windows.any(|window| is_match(&window.title)),
+            Field::Class => windows.any(|window| is_match(&window.class)),
+            Field::Args => is_match(&info.args.join(" ")),
+        }
+    }
+}
+
+/// The first rule that matches the process, if any.
+pub fn first_match<'a>(
+    rules: impl IntoIterator<Item = &'a Rule>,
+    process: &TrackedProcess,
+) -> Option<&'a Rule> {
+    rules.into_iter().find(|rule| rule.matches(process))
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn glob(field: Field, pattern: &str) -> Result<Regex, RuleError> {
+    let glob = GlobBuilder::new(pattern)
+        // `*` stays within a directory, `**` doesn't
+        .literal_separator(field == Field::Path)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| RuleError(format!("bad glob: {e}")))?;
+    Regex::new(glob.regex()).map_err(|e| RuleError(format!("bad glob: {e}")))
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let source = source.trim();
+        if source.is_empty() {
+            return Err(RuleError("empty rule".to_owned()));
+        }
+
+        let parsed = source.split_once('=').and_then(|(key, value)| {
+            let (key, regex) = match key.strip_suffix('~') {
+                Some(key) => (key, true),
+                None => (key, false),
+            };
+            Field::from_key(key.trim()).map(|field| (field, regex, unquote(value.trim())))
+        });
+
+        let (field, pattern) = match parsed {
+            Some((field, true, value)) => (
+                field,
+                Regex::new(value).map_err(|e| RuleError(format!("bad regex: {e}")))?,
+            ),
+            Some((field, false, value)) => (field, glob(field, value)?),
+            // anything that isn't `field=...` is a process name
+            None => (Field::Name, glob(Field::Name, source)?),
+        };
+
+        Ok(Self {
+            source: source.to_owned(),
+            field,
+            pattern,
+        })
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = RuleError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<Rule> for String {
+    fn from(rule: Rule) -> Self {
+        rule.source
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+// two rules are the same if they were typed the same, that's all a set of them needs
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Rule {}
+
+impl PartialOrd for Rule {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rule {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.source.cmp(&other.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::Rule;
+    use crate::processes::{ProcessInfo, TrackedProcess, TrackedWindow};
+
+    fn process(info: ProcessInfo, title: &str) -> TrackedProcess {
+        TrackedProcess {
+            info,
+            windows: vec![TrackedWindow {
+                id: 1,
+                title: title.to_owned(),
+                class: "jetbrains-idea".to_owned(),
+                visible: true,
+                in_taskbar: true,
+            }],
+        }
+    }
+
+    fn rule(source: &str) -> Rule {
+        source
+            .parse()
+            .unwrap_or_else(|e| panic!("{source} should parse: {e}"))
+    }
+
+    #[test]
+    fn plain_names_still_work() {
+        let code = process(ProcessInfo::new("code.exe"), "");
+        assert!(rule("code.exe").matches(&code), "exact name");
+        assert!(rule("Code.exe").matches(&code), "names ignore case");
+        assert!(!rule("code").matches(&code), "but not half a name");
+        assert!(rule("code.exe").is_plain(), "shown like a name");
+    }
+
+    #[test]
+    fn path_globs_only_cross_directories_with_two_stars() {
+        let idea = process(
+            ProcessInfo {
+                exe: Some(PathBuf::from("/opt/jetbrains/idea/bin/idea")),
+                ..ProcessInfo::new("idea")
+            },
+            "",
+        );
+        assert!(
+            rule("path=/opt/jetbrains/**").matches(&idea),
+            "** goes deep"
+        );
+        assert!(
+            !rule("path=/opt/jetbrains/*").matches(&idea),
+            "* stays in one directory"
+        );
+        assert!(
+            !rule("path=/opt/**").matches(&process(ProcessInfo::new("idea"), "")),
+            "no path, no match"
+        );
+    }
+
+    #[test]
+    fn title_regex_matches_any_visible_window() {
+        let code = process(ProcessInfo::new("code"), "main.rs - Visual Studio Code");
+        assert!(
+            rule(r#"title~=".*- Visual Studio Code$""#).matches(&code),
+            "quoted regex"
+        );
+        assert!(!rule("title~=^Visual").matches(&code), "anchored regex");
+    }
+
+    #[test]
+    fn class_and_args() {
+        let idea = process(
+            ProcessInfo {
+                args: vec!["--profile".to_owned(), "work".to_owned()],
+                ..ProcessInfo::new("idea")
+            },
+            "",
+        );
+        assert!(rule("class=jetbrains-*").matches(&idea), "class glob");
+        assert!(rule("app_id=jetbrains-idea").matches(&idea), "app_id alias");
+        assert!(rule("args~=--profile work").matches(&idea), "joined args");
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        assert!("title~=(".parse::<Rule>().is_err(), "unclosed group");
+        assert!("path=[".parse::<Rule>().is_err(), "unclosed class");
+        assert!("  ".parse::<Rule>().is_err(), "empty");
+    }
+
+    #[test]
+    fn names_with_glob_characters_are_escaped() {
+        let odd = process(ProcessInfo::new("app[1].exe"), "");
+        let rule = Rule::name("app[1].exe");
+        assert!(rule.matches(&odd), "matches itself");
+        assert_eq!(
+            rule.as_str().parse::<Rule>().as_ref(),
+            Ok(&rule),
+            "and survives a round trip"
+        );
+    }
+}
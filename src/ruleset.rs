@@ -0,0 +1,342 @@
+//! Allowlists and killlists on their own, to share a curated list with the team.
+//!
+//! A rule set is a TOML file with every entry's comment next to it. Importing one into a
+//! profile adds whatever's new and finds where the two disagree, a rule on the other list
+//! or with another comment. Those stay ours, go the file's way, or get picked one by one.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::ConfigError;
+use crate::profiles::Profile;
+use crate::rules::Rule;
+
+/// What [`RuleSet::version`] is for files written by this build.
+pub const VERSION: u32 = 1;
+
+/// A rule and why it's there.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Entry {
+    pub rule: Rule,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RuleSet {
+    pub version: u32,
+    // toml can't have an empty one after the other's tables
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allowlist: Vec<Entry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub killlist: Vec<Entry>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            allowlist: Vec::new(),
+            killlist: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum List {
+    Allow,
+    Kill,
+}
+
+impl List {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Allow => "allowlist",
+            Self::Kill => "killlist",
+        }
+    }
+
+    fn of(self, profile: &mut Profile) -> &mut BTreeSet<Rule> {
+        match self {
+            Self::Allow => &mut profile.allowlist,
+            Self::Kill => &mut profile.killlist,
+        }
+    }
+
+    // the killlist wins if it's on both, same as when sweeping
+    fn holding(profile: &Profile, rule: &Rule) -> Option<Self> {
+        if profile.killlist.contains(rule) {
+            Some(Self::Kill)
+        } else if profile.allowlist.contains(rule) {
+            Some(Self::Allow)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where one side has a rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Side {
+    pub list: List,
+    pub comment: String,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.list.label())?;
+        if !self.comment.is_empty() {
+            write!(f, " ({:?})", self.comment)?;
+        }
+        Ok(())
+    }
+}
+
+/// A rule the profile and the rule set disagree on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub rule: Rule,
+    pub ours: Side,
+    pub theirs: Side,
+    /// Which way it goes, ours unless someone says otherwise.
+    pub take_theirs: bool,
+}
+
+/// What importing a rule set would do to a profile.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Merge {
+    /// New to the profile (or only their comment is), nothing to decide.
+    pub added: Vec<(List, Entry)>,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn add(profile: &mut Profile, list: List, entry: &Entry) {
+    list.of(profile).insert(entry.rule.clone());
+    if !entry.comment.is_empty() {
+        profile
+            .comments
+            .insert(entry.rule.clone(), entry.comment.clone());
+    }
+}
+
+impl Merge {
+    /// Everything new, conflicts going whichever way they're set to.
+    pub fn apply(&self, profile: &mut Profile) {
+        for (list, entry) in &self.added {
+            add(profile, *list, entry);
+        }
+        for conflict in self
+            .conflicts
+            .iter()
+            .filter(|conflict| conflict.take_theirs)
+        {
+            profile.allowlist.remove(&conflict.rule);
+            profile.killlist.remove(&conflict.rule);
+            let entry = Entry {
+                rule: conflict.rule.clone(),
+                comment: conflict.theirs.comment.clone(),
+            };
+            add(profile, conflict.theirs.list, &entry);
+        }
+    }
+}
+
+impl RuleSet {
+    /// The profile's lists, comments and all.
+    pub fn export(profile: &Profile) -> Self {
+        let entries = |rules: &BTreeSet<Rule>| {
+            rules
+                .iter()
+                .map(|rule| Entry {
+                    rule: rule.clone(),
+                    comment: profile.comment(rule).to_owned(),
+                })
+                .collect()
+        };
+        Self {
+            version: VERSION,
+            allowlist: entries(&profile.allowlist),
+            killlist: entries(&profile.killlist),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (List, &Entry)> {
+        let allow = self.allowlist.iter().map(|entry| (List::Allow, entry));
+        let kill = self.killlist.iter().map(|entry| (List::Kill, entry));
+        allow.chain(kill)
+    }
+
+    /// What's new to the profile and where the two disagree. A comment on one side only
+    /// isn't a disagreement, theirs just gets added.
+    pub fn compare(&self, profile: &Profile) -> Merge {
+        let mut merge = Merge::default();
+        for (list, entry) in self.entries() {
+            let Some(ours) = List::holding(profile, &entry.rule) else {
+                merge.added.push((list, entry.clone()));
+                continue;
+            };
+            let comment = profile.comment(&entry.rule);
+            let comments_differ =
+                !comment.is_empty() && !entry.comment.is_empty() && comment != entry.comment;
+            if ours != list || comments_differ {
+                merge.conflicts.push(Conflict {
+                    rule: entry.rule.clone(),
+                    ours: Side {
+                        list: ours,
+                        comment: comment.to_owned(),
+                    },
+                    theirs: Side {
+                        list,
+                        comment: entry.comment.clone(),
+                    },
+                    take_theirs: false,
+                });
+            } else if comment.is_empty() && !entry.comment.is_empty() {
+                merge.added.push((list, entry.clone()));
+            }
+        }
+        merge
+    }
+
+    /// Makes the profile's lists exactly this.
+    pub fn replace(&self, profile: &mut Profile) {
+        profile.allowlist.clear();
+        profile.killlist.clear();
+        profile.comments.clear();
+        for (list, entry) in self.entries() {
+            add(profile, list, entry);
+        }
+    }
+}
+
+impl std::str::FromStr for RuleSet {
+    type Err = ConfigError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let set: Self = toml::from_str(text)?;
+        if set.version > VERSION {
+            return Err(ConfigError::TooNew(set.version));
+        }
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, List, RuleSet};
+    use crate::profiles::Profile;
+    use crate::rules::Rule;
+
+    fn entry(rule: &str, comment: &str) -> Entry {
+        Entry {
+            rule: rule.parse().expect("valid rule"),
+            comment: comment.to_owned(),
+        }
+    }
+
+    fn ours() -> Profile {
+        let mut profile = Profile::default();
+        profile.allowlist.insert(Rule::name("code.exe"));
+        profile.allowlist.insert(Rule::name("slack.exe"));
+        profile.killlist.insert(Rule::name("steam.exe"));
+        profile
+            .comments
+            .insert(Rule::name("code.exe"), "the editor".to_owned());
+        profile
+    }
+
+    fn theirs() -> RuleSet {
+        RuleSet {
+            allowlist: vec![
+                entry("code.exe", "everyone's IDE"),
+                entry("steam.exe", "it's friday"),
+                entry("path=/opt/jetbrains/**", "the other IDEs"),
+            ],
+            killlist: vec![entry("slack.exe", "")],
+            ..RuleSet::default()
+        }
+    }
+
+    #[test]
+    fn comments_survive_the_trip() {
+        let text = r#"
+            version = 1
+
+            [[allowlist]]
+            rule = "code.exe"
+            comment = "everyone's IDE"
+
+            [[killlist]]
+            rule = "title~=.*Steam$"
+        "#;
+        let set: RuleSet = text.parse().expect("a valid rule set");
+        let mut profile = Profile::default();
+        set.replace(&mut profile);
+        assert_eq!(RuleSet::export(&profile), set, "nothing lost");
+
+        let written = toml::to_string_pretty(&set).expect("serializes");
+        assert_eq!(
+            written.parse::<RuleSet>().ok(),
+            Some(set),
+            "and back:\n{written}"
+        );
+        assert!(
+            "version = 2".parse::<RuleSet>().is_err(),
+            "not from the future"
+        );
+    }
+
+    #[test]
+    fn merging_keeps_ours_unless_told_otherwise() {
+        let mut merge = theirs().compare(&ours());
+        assert_eq!(
+            merge.added,
+            [(
+                List::Allow,
+                entry("path=/opt/jetbrains/**", "the other IDEs")
+            )],
+            "only the new one goes in right away"
+        );
+        assert_eq!(
+            merge
+                .conflicts
+                .iter()
+                .map(|conflict| conflict.rule.as_str())
+                .collect::<Vec<_>>(),
+            ["code.exe", "steam.exe", "slack.exe"],
+            "another comment, and two on the other list"
+        );
+
+        let mut union = ours();
+        merge.apply(&mut union);
+        assert!(union.killlist.contains(&Rule::name("steam.exe")), "ours");
+        assert_eq!(union.comment(&Rule::name("code.exe")), "the editor", "ours");
+
+        merge.conflicts[1].take_theirs = true;
+        let mut reviewed = ours();
+        merge.apply(&mut reviewed);
+        assert!(
+            reviewed.allowlist.contains(&Rule::name("steam.exe"))
+                && !reviewed.killlist.contains(&Rule::name("steam.exe")),
+            "moved over"
+        );
+        assert_eq!(
+            reviewed.comment(&Rule::name("steam.exe")),
+            "it's friday",
+            "with their comment"
+        );
+    }
+}
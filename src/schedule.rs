@@ -0,0 +1,350 @@
+//! Days of the week and times of day, e.g. `Mon-Fri 09:00-17:30`. Stored as typed, the
+//! same way rules are.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use jiff::civil::{DateTime, Time, Weekday};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleError(String);
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+fn day_index(day: Weekday) -> usize {
+    usize::try_from(day.to_monday_zero_offset()).unwrap_or_default()
+}
+
+/// Which days of the week, Monday first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Days([bool; 7]);
+
+impl Days {
+    pub const EVERY: Self = Self([true; 7]);
+
+    pub fn contains(self, day: Weekday) -> bool {
+        self.0[day_index(day)]
+    }
+}
+
+fn day(name: &str) -> Result<usize, ScheduleError> {
+    let name = name.trim().to_lowercase();
+    DAY_NAMES
+        .iter()
+        .position(|day| name.starts_with(day))
+        .ok_or_else(|| ScheduleError(format!("not a day: {name:?}")))
+}
+
+impl FromStr for Days {
+    type Err = ScheduleError;
+
+    /// `Mon-Fri`, `Sat,Sun`, `weekdays`, `weekends` or `daily`. Ranges can wrap, `Fri-Mon`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.trim().to_lowercase().as_str() {
+            "daily" | "*" => return Ok(Self::EVERY),
+            "weekdays" => return Ok(Self([true, true, true, true, true, false, false])),
+            "weekends" => return Ok(Self([false, false, false, false, false, true, true])),
+            _ => {}
+        }
+        let mut days = [false; 7];
+        for part in text.split(',') {
+            match part.split_once('-') {
+                Some((from, to)) => {
+                    let (mut i, to) = (day(from)?, day(to)?);
+                    days[i] = true;
+                    while i != to {
+                        i = (i + 1) % 7;
+                        days[i] = true;
+                    }
+                }
+                None => days[day(part)?] = true,
+            }
+        }
+        Ok(Self(days))
+    }
+}
+
+fn time(text: &str) -> Result<Time, ScheduleError> {
+    text.trim()
+        .parse()
+        .map_err(|e| ScheduleError(format!("bad time {text:?}: {e}")))
+}
+
+// days first if there are any, `Mon-Fri 18:30` or just `18:30`
+fn split_days(text: &str) -> Result<(Days, &str), ScheduleError> {
+    match text.trim().rsplit_once(' ') {
+        Some((days, rest)) => Ok((days.parse()?, rest)),
+        None => Ok((Days::EVERY, text.trim())),
+    }
+}
+
+/// A stretch of time on some days, `Mon-Fri 09:00-17:30`, every day without the days.
+/// One that ends before it starts goes past midnight.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hours {
+    source: String,
+    days: Days,
+    from: Time,
+    to: Time,
+}
+
+impl Hours {
+    pub fn contains(&self, at: DateTime) -> bool {
+        let (day, now) = (at.weekday(), at.time());
+        if self.from <= self.to {
+            return self.days.contains(day) && self.from <= now && now < self.to;
+        }
+        // overnight, the early hours belong to the day before
+        (self.days.contains(day) && now >= self.from)
+            || (self.days.contains(day.previous()) && now < self.to)
+    }
+}
+
+impl FromStr for Hours {
+    type Err = ScheduleError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let (days, range) = split_days(source)?;
+        let (from, to) = range
+            .split_once('-')
+            .ok_or_else(|| ScheduleError(format!("expected from-to, got {range:?}")))?;
+        Ok(Self {
+            source: source.trim().to_owned(),
+            days,
+            from: time(from)?,
+            to: time(to)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Hours {
+    type Error = ScheduleError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<Hours> for String {
+    fn from(hours: Hours) -> Self {
+        hours.source
+    }
+}
+
+impl fmt::Display for Hours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl PartialEq for Hours {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Hours {}
+
+/// What sets a scheduled sweep off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// A time of day on some days.
+    At { days: Days, time: Time },
+    /// Nobody touching the keyboard or mouse for this long.
+    Idle(Duration),
+}
+
+/// When a scheduled sweep runs, `Mon-Fri 18:30`, `18:30` for every day, or `idle 15`
+/// for after 15 minutes of nobody around.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct When {
+    source: String,
+    trigger: Trigger,
+}
+
+impl When {
+    pub fn trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    /// The first time strictly after `after` that it's due, `None` for idle ones.
+    pub fn next_after(&self, after: DateTime) -> Option<DateTime> {
+        let Trigger::At { days, time } = self.trigger else {
+            return None;
+        };
+        let mut date = after.date();
+        // today might be too late already, a week later never is
+        for _ in 0..8 {
+            let at = date.to_datetime(time);
+            if days.contains(date.weekday()) && at > after {
+                return Some(at);
+            }
+            date = date.tomorrow().ok()?;
+        }
+        None
+    }
+}
+
+impl FromStr for When {
+    type Err = ScheduleError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let trigger = if let Some(minutes) = source.trim().strip_prefix("idle ") {
+            let minutes: u64 = minutes
+                .trim()
+                .trim_end_matches('m')
+                .parse()
+                .map_err(|e| ScheduleError(format!("bad minutes {minutes:?}: {e}")))?;
+            if minutes == 0 {
+                return Err(ScheduleError("idle for at least a minute".to_owned()));
+            }
+            let secs = minutes
+                .checked_mul(60)
+                .ok_or_else(|| ScheduleError(format!("{minutes} minutes is too long")))?;
+            Trigger::Idle(Duration::from_secs(secs))
+        } else {
+            let (days, at) = split_days(source)?;
+            Trigger::At {
+                days,
+                time: time(at)?,
+            }
+        };
+        Ok(Self {
+            source: source.trim().to_owned(),
+            trigger,
+        })
+    }
+}
+
+impl TryFrom<String> for When {
+    type Error = ScheduleError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+impl From<When> for String {
+    fn from(when: When) -> Self {
+        when.source
+    }
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl PartialEq for When {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for When {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use jiff::civil::date;
+
+    use super::{Days, Hours, Trigger, When};
+
+    fn hours(source: &str) -> Hours {
+        source.parse().expect("valid hours")
+    }
+
+    #[test]
+    fn days_read_like_you_would_write_them() {
+        assert_eq!(
+            "Mon-Fri".parse::<Days>(),
+            "weekdays".parse::<Days>(),
+            "same thing"
+        );
+        assert_eq!(
+            "Sat,Sunday".parse::<Days>(),
+            "weekends".parse::<Days>(),
+            "full names too"
+        );
+        assert_eq!(
+            "Fri-Mon".parse::<Days>(),
+            "fri,sat,sun,mon".parse::<Days>(),
+            "ranges wrap around"
+        );
+        assert!("Mon-Fry".parse::<Days>().is_err(), "not a day");
+    }
+
+    #[test]
+    fn work_hours() {
+        let work = hours("Mon-Fri 09:00-17:30");
+        // 2026-10-19 is a monday
+        assert!(
+            work.contains(date(2026, 10, 19).at(9, 0, 0, 0)),
+            "starts at 9"
+        );
+        assert!(
+            !work.contains(date(2026, 10, 19).at(17, 30, 0, 0)),
+            "done at half past 5"
+        );
+        assert!(
+            !work.contains(date(2026, 10, 18).at(12, 0, 0, 0)),
+            "not on sunday"
+        );
+        assert!(
+            hours("12:00-13:00").contains(date(2026, 10, 18).at(12, 30, 0, 0)),
+            "every day without days"
+        );
+    }
+
+    #[test]
+    fn nights_go_past_midnight() {
+        let nights = hours("Fri 22:00-02:00");
+        assert!(
+            nights.contains(date(2026, 10, 24).at(1, 0, 0, 0)),
+            "saturday 1am is still friday night"
+        );
+        assert!(
+            !nights.contains(date(2026, 10, 24).at(23, 0, 0, 0)),
+            "saturday night isn't"
+        );
+    }
+
+    #[test]
+    fn next_weekday_evening() {
+        let when: When = "Mon-Fri 18:30".parse().expect("valid time");
+        // 2026-10-23 is a friday
+        assert_eq!(
+            when.next_after(date(2026, 10, 23).at(12, 0, 0, 0)),
+            Some(date(2026, 10, 23).at(18, 30, 0, 0)),
+            "later today"
+        );
+        assert_eq!(
+            when.next_after(date(2026, 10, 23).at(18, 30, 0, 0)),
+            Some(date(2026, 10, 26).at(18, 30, 0, 0)),
+            "then monday, skipping the weekend"
+        );
+        assert_eq!(
+            "idle 15".parse::<When>().map(|when| when.trigger()),
+            Ok(Trigger::Idle(Duration::from_secs(15 * 60))),
+            "minutes"
+        );
+        assert!("idle 0".parse::<When>().is_err(), "that'd be always");
+        assert!(
+            "idle 999999999999999999".parse::<When>().is_err(),
+            "too long to count"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Sweeps that run by themselves, at set times or once nobody's around.
+//!
+//! The time is kept on a thread of its own, and whatever comes due goes to the worker as a
+//! profile name. That sweeps by itself like it does for hotkeys, whether or not the window
+//! is painting, only sweeps with a countdown wait for the window.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use jiff::civil::DateTime;
+
+use crate::backend::WindowBackend;
+use crate::schedule::{Trigger, When};
+
+// how often the thread looks at the clock
+const TICK: Duration = Duration::from_secs(1);
+
+// timed sweeps missed by more than this (the pc was asleep, say) are skipped, nobody
+// wants their morning apps closed by yesterday's 18:30
+const MISSED_BY: Duration = Duration::from_secs(60);
+
+/// A sweep with `profile` whenever it's `when`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ScheduledSweep {
+    pub when: When,
+    pub profile: String,
+}
+
+/// The next timed sweep after `now`, idle ones don't have a time.
+pub fn next_sweep(sweeps: &[ScheduledSweep], now: DateTime) -> Option<(DateTime, &ScheduledSweep)> {
+    sweeps
+        .iter()
+        .filter_map(|sweep| Some((sweep.when.next_after(now)?, sweep)))
+        .min_by_key(|(at, _)| *at)
+}
+
+// what the thread keeps between ticks
+struct Clock {
+    sweeps: Vec<ScheduledSweep>,
+    last: DateTime,
+    // idle sweeps go off once per stretch of idleness, not on every tick of it
+    idle_fired: Vec<bool>,
+}
+
+impl Clock {
+    fn new(sweeps: Vec<ScheduledSweep>, now: DateTime) -> Self {
+        Self {
+            idle_fired: vec![false; sweeps.len()],
+            sweeps,
+            last: now,
+        }
+    }
+
+    // sweeps that stay keep their idle stretch, a new config doesn't make them go off again
+    fn set(&mut self, sweeps: Vec<ScheduledSweep>) {
+        self.idle_fired = sweeps
+            .iter()
+            .map(|sweep| {
+                self.sweeps
+                    .iter()
+                    .zip(&self.idle_fired)
+                    .any(|(old, fired)| old == sweep && *fired)
+            })
+            .collect();
+        self.sweeps = sweeps;
+    }
+
+    fn wants_idle(&self) -> bool {
+        self.sweeps
+            .iter()
+            .any(|sweep| matches!(sweep.when.trigger(), Trigger::Idle(_)))
+    }
+
+    // the profiles of whatever came due since the last tick
+    fn tick(&mut self, now: DateTime, idle: Option<Duration>) -> Vec<String> {
+        let from = now
+            .checked_sub(MISSED_BY)
+            .map_or(self.last, |earliest| earliest.max(self.last));
+        let mut due = Vec::new();
+        for (sweep, fired) in self.sweeps.iter().zip(&mut self.idle_fired) {
+            match sweep.when.trigger() {
+                Trigger::At { .. } => {
+                    if sweep.when.next_after(from).is_some_and(|at| at <= now) {
+                        due.push(sweep.profile.clone());
+                    }
+                }
+                Trigger::Idle(after) => {
+                    let idle = idle.is_some_and(|idle| idle >= after);
+                    if idle && !*fired {
+                        due.push(sweep.profile.clone());
+                    }
+                    *fired = idle;
+                }
+            }
+        }
+        self.last = now;
+        due
+    }
+}
+
+/// Handle to the scheduler thread, does nothing if there isn't one.
+#[derive(Default)]
+pub struct Scheduler {
+    tx: Option<Sender<Vec<ScheduledSweep>>>,
+}
+
+impl Scheduler {
+    /// `due` gets the profile of every sweep that comes due, it says false once nobody's
+    /// listening anymore.
+    pub fn spawn(
+        sweeps: Vec<ScheduledSweep>,
+        backend: Arc<dyn WindowBackend>,
+        due: impl Fn(String) -> bool + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || run(sweeps, &*backend, &rx, &due));
+        Self { tx: Some(tx) }
+    }
+
+    /// Replaces the schedule.
+    pub fn set(&self, sweeps: Vec<ScheduledSweep>) {
+        if let Some(tx) = &self.tx {
+            tx.send(sweeps).ok();
+        }
+    }
+}
+
+fn run(
+    sweeps: Vec<ScheduledSweep>,
+    backend: &dyn WindowBackend,
+    rx: &Receiver<Vec<ScheduledSweep>>,
+    due: &dyn Fn(String) -> bool,
+) {
+    let mut clock = Clock::new(sweeps, jiff::Zoned::now().datetime());
+    loop {
+        match rx.recv_timeout(TICK) {
+            Ok(sweeps) => clock.set(sweeps),
+            Err(RecvTimeoutError::Timeout) => {}
+            // the app's gone
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        let idle = if clock.wants_idle() {
+            backend.idle_time()
+        } else {
+            None
+        };
+        for profile in clock.tick(jiff::Zoned::now().datetime(), idle) {
+            log::info!("Scheduled sweep with {profile}");
+            if !due(profile) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use jiff::civil::date;
+
+    use super::{Clock, ScheduledSweep, next_sweep};
+
+    fn sweep(when: &str, profile: &str) -> ScheduledSweep {
+        ScheduledSweep {
+            when: when.parse().expect("valid schedule"),
+            profile: profile.to_owned(),
+        }
+    }
+
+    #[test]
+    fn timed_sweeps_go_off_once() {
+        let evening = date(2026, 10, 19);
+        let mut clock = Clock::new(
+            vec![sweep("Mon-Fri 18:30", "end of day")],
+            evening.at(18, 29, 59, 0),
+        );
+        assert_eq!(
+            clock.tick(evening.at(18, 30, 0, 0), None),
+            ["end of day"],
+            "right on time"
+        );
+        assert!(
+            clock.tick(evening.at(18, 30, 1, 0), None).is_empty(),
+            "and not again a second later"
+        );
+
+        let mut asleep = Clock::new(
+            vec![sweep("Mon-Fri 18:30", "end of day")],
+            evening.at(18, 0, 0, 0),
+        );
+        assert!(
+            asleep
+                .tick(date(2026, 10, 20).at(9, 0, 0, 0), None)
+                .is_empty(),
+            "missed ones are skipped"
+        );
+    }
+
+    #[test]
+    fn idle_sweeps_wait_for_someone_to_come_back() {
+        let now = date(2026, 10, 19).at(12, 0, 0, 0);
+        let mut clock = Clock::new(vec![sweep("idle 15", "away")], now);
+        let minutes = |minutes: u64| Some(Duration::from_secs(minutes * 60));
+
+        assert!(clock.tick(now, minutes(14)).is_empty(), "not yet");
+        assert_eq!(clock.tick(now, minutes(15)), ["away"], "now");
+        assert!(clock.tick(now, minutes(16)).is_empty(), "only once");
+        clock.tick(now, minutes(0));
+        assert_eq!(clock.tick(now, minutes(15)), ["away"], "and once more");
+
+        clock.set(vec![sweep("idle 15", "away"), sweep("idle 5", "short")]);
+        assert_eq!(
+            clock.tick(now, minutes(16)),
+            ["short"],
+            "a new config doesn't set the old one off again"
+        );
+    }
+
+    #[test]
+    fn the_next_one_is_the_soonest() {
+        let sweeps = [
+            sweep("idle 15", "away"),
+            sweep("Sat 10:00", "weekend"),
+            sweep("Mon-Fri 18:30", "end of day"),
+        ];
+        let next = next_sweep(&sweeps, date(2026, 10, 23).at(19, 0, 0, 0));
+        assert_eq!(
+            next.map(|(at, sweep)| (at, sweep.profile.as_str())),
+            Some((date(2026, 10, 24).at(10, 0, 0, 0), "weekend")),
+            "friday evening's done, saturday's next"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! What a sweep closed, kept so it can be opened again the next morning.
+//!
+//! Snapshots live in their own file next to the app state, so the window and the command
+//! line can both add to it without one overwriting the other on exit.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::processes::ProcessInfo;
+
+/// Where each sweep's snapshot goes, replacing the one before.
+pub const LAST_SESSION: &str = "last session";
+
+/// An app as it was when it got closed, enough to start it again.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SavedApp {
+    pub name: String,
+    pub exe: PathBuf,
+    /// Without the executable.
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// Only for showing what it was, apps open their own windows.
+    pub titles: Vec<String>,
+}
+
+impl SavedApp {
+    /// `None` if we're not allowed to see where its executable is.
+    pub fn capture(info: &ProcessInfo, titles: Vec<String>) -> Option<Self> {
+        Some(Self {
+            name: info.name.clone(),
+            exe: info.exe.clone()?,
+            args: info.args.clone(),
+            cwd: info.cwd.clone(),
+            titles,
+        })
+    }
+
+    /// Starts it detached from us, with nothing attached to its stdio.
+    pub fn launch(&self) -> io::Result<()> {
+        let mut command = Command::new(&self.exe);
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(cwd) = self.cwd.as_deref().filter(|cwd| cwd.is_dir()) {
+            command.current_dir(cwd);
+        }
+        let mut child = command.spawn()?;
+        // reaping it whenever it exits, so it doesn't linger as a zombie
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// The apps one sweep closed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Snapshot {
+    /// Seconds since the epoch.
+    pub taken: u64,
+    pub apps: Vec<SavedApp>,
+}
+
+impl Snapshot {
+    pub fn new(apps: Vec<SavedApp>) -> Self {
+        let taken = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self { taken, apps }
+    }
+
+    /// Launches every app, returns the ones that failed and why.
+    pub fn restore(&self) -> Vec<(&SavedApp, io::Error)> {
+        self.apps
+            .iter()
+            .filter_map(|app| {
+                app.launch()
+                    .inspect(|()| log::info!("Restored {}", app.name))
+                    .err()
+                    .map(|e| (app, e))
+            })
+            .collect()
+    }
+}
+
+/// Snapshots by name, the one from the last sweep under [`LAST_SESSION`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Sessions {
+    pub snapshots: BTreeMap<String, Snapshot>,
+}
+
+impl Sessions {
+    /// Next to where eframe keeps the app state.
+    pub fn default_path() -> Option<PathBuf> {
+        eframe::storage_dir("expurgate").map(|dir| dir.join("sessions.ron"))
+    }
+
+    /// Empty if there's nothing saved yet, or it's unreadable.
+    pub fn load(path: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        ron::from_str(&text)
+            .inspect_err(|e| log::warn!("Failed to read {}: {e}", path.display()))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    /// Loads whatever is on disk, changes it and writes it back, so nobody else's
+    /// snapshots get lost in between. Without a path it only lives in memory.
+    pub fn update(&mut self, path: Option<&Path>, change: impl FnOnce(&mut Self)) {
+        if let Some(path) = path {
+            *self = Self::load(path);
+        }
+        change(self);
+        if let Some(path) = path {
+            if let Err(e) = self.save(path) {
+                log::warn!("Failed to save {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Keeps what a sweep closed as the [`LAST_SESSION`].
+    pub fn remember_sweep(&mut self, path: Option<&Path>, apps: Vec<SavedApp>) {
+        let snapshot = Snapshot::new(apps);
+        self.update(path, |sessions| {
+            sessions.snapshots.insert(LAST_SESSION.to_owned(), snapshot);
+        });
+    }
+
+    /// Keeps a copy of a snapshot under another name, false if there's no such snapshot.
+    pub fn keep_as(&mut self, from: &str, name: &str) -> bool {
+        let Some(snapshot) = self.snapshots.get(from).cloned() else {
+            return false;
+        };
+        self.snapshots.insert(name.to_owned(), snapshot);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LAST_SESSION, SavedApp, Sessions, Snapshot};
+
+    fn app(name: &str) -> SavedApp {
+        SavedApp {
+            name: name.to_owned(),
+            exe: format!("/usr/bin/{name}").into(),
+            args: vec!["--new-window".to_owned()],
+            cwd: None,
+            titles: vec![format!("{name} window")],
+        }
+    }
+
+    #[test]
+    fn snapshots_survive_a_round_trip() {
+        let dir = std::env::temp_dir().join(format!("expurgate-sessions-{}", std::process::id()));
+        let path = dir.join("sessions.ron");
+
+        let mut sessions = Sessions::default();
+        sessions.update(Some(&path), |sessions| {
+            sessions
+                .snapshots
+                .insert(LAST_SESSION.to_owned(), Snapshot::new(vec![app("gedit")]));
+        });
+        // someone else adding theirs in the meantime
+        let mut other = Sessions::load(&path);
+        other.update(Some(&path), |sessions| {
+            assert!(
+                sessions.keep_as(LAST_SESSION, "monday"),
+                "there's a last one"
+            );
+        });
+        sessions.update(Some(&path), |_| {});
+
+        assert_eq!(
+            sessions.snapshots.keys().collect::<Vec<_>>(),
+            ["last session", "monday"],
+            "both are kept"
+        );
+        assert_eq!(
+            sessions.snapshots["monday"].apps,
+            [app("gedit")],
+            "with everything needed to relaunch"
+        );
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn restoring_reports_what_failed() {
+        let snapshot = Snapshot::new(vec![SavedApp {
+            exe: "/nonexistent/expurgate-test-app".into(),
+            ..app("ghost")
+        }]);
+        let failed = snapshot.restore();
+        assert_eq!(failed.len(), 1, "there's nothing to launch");
+        assert_eq!(failed[0].0.name, "ghost", "and it says which");
+    }
+}
@@ -0,0 +1,225 @@
+#[cfg(windows)]
+use std::sync::mpsc;
+#[cfg(windows)]
+use std::sync::mpsc::{Receiver, Sender};
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use tray_item::{IconSource, TrayItem};
+
+#[cfg(windows)]
+enum Message {
+    Quit,
+    Hide,
+    Unhide,
+    Tooltip(String),
+    ActiveProfile(String),
+}
+
+/// What the tray menu asks of the app.
+#[derive(Debug)]
+#[cfg_attr(
+    not(windows),
+    expect(dead_code, reason = "only the windows tray sends these")
+)]
+pub enum TrayEvent {
+    SwitchProfile(String),
+    RestoreLastSession,
+    Undo,
+}
+
+/// Handle to the tray thread, does nothing if there's no tray.
+#[derive(Default)]
+pub struct Tray {
+    #[cfg(windows)]
+    tx: Option<Sender<Message>>,
+    #[cfg(windows)]
+    events: Option<Receiver<TrayEvent>>,
+}
+
+#[cfg(windows)]
+impl Tray {
+    pub fn set_tooltip(&self, tooltip: &str) {
+        if let Some(tx) = &self.tx {
+            tx.send(Message::Tooltip(tooltip.to_owned())).ok();
+        }
+    }
+
+    pub fn set_active_profile(&self, name: &str) {
+        if let Some(tx) = &self.tx {
+            tx.send(Message::ActiveProfile(name.to_owned())).ok();
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<TrayEvent> {
+        self.events.as_ref()?.try_recv().ok()
+    }
+}
+
+#[cfg(not(windows))]
+impl Tray {
+    #[expect(clippy::unused_self, reason = "no tray to put it on")]
+    pub fn set_tooltip(&self, _tooltip: &str) {}
+
+    #[expect(clippy::unused_self, reason = "no tray to put it on")]
+    pub fn set_active_profile(&self, _name: &str) {}
+
+    #[expect(clippy::unused_self, reason = "no tray to click on")]
+    pub fn try_recv(&self) -> Option<TrayEvent> {
+        None
+    }
+}
+
+#[cfg(windows)]
+fn profile_label(name: &str, active: bool) -> String {
+    if active {
+        format!("{name} (active)")
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Undoes the tray's hide, also what the show window hotkey does.
+pub fn show_window(ctx: &egui::Context) {
+    let viewport = egui::ViewportId::ROOT;
+    ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::Decorations(true));
+    ctx.send_viewport_cmd_to(
+        viewport,
+        egui::ViewportCommand::InnerSize([350.0, 480.0].into()),
+    );
+    ctx.send_viewport_cmd_to(
+        viewport,
+        egui::ViewportCommand::OuterPosition(egui::pos2(200.0, 200.0)),
+    );
+    ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::Focus);
+    ctx.request_repaint();
+}
+
+// tray-item is windows only for us, elsewhere there's just the window
+#[cfg(not(windows))]
+pub fn spawn(_ctx: egui::Context, _profiles: Vec<String>, _active: &str) -> Tray {
+    Tray::default()
+}
+
+/// The profiles only go in the menu once, new ones show up after a restart.
+#[cfg(windows)]
+pub fn spawn(ctx: egui::Context, profiles: Vec<String>, active: &str) -> Tray {
+    let (tx, rx) = mpsc::channel();
+    let (event_tx, event_rx) = mpsc::channel();
+    let handle = Tray {
+        tx: Some(tx.clone()),
+        events: Some(event_rx),
+    };
+    let active = active.to_owned();
+
+    thread::spawn(move || {
+        let mut tray = TrayItem::new("Tray", IconSource::Resource("icon")).unwrap();
+
+        tray.add_label("Tray Label").unwrap();
+
+        tray.add_menu_item("Hello", || {
+            println!("Hello!");
+        })
+        .unwrap();
+
+        tray.inner_mut().add_separator().unwrap();
+
+        let mut profile_items = Vec::new();
+        for name in profiles {
+            let event_tx = event_tx.clone();
+            let ctx = ctx.clone();
+            let switch_to = name.clone();
+            let id = tray
+                .inner_mut()
+                .add_menu_item_with_id(&profile_label(&name, name == active), move || {
+                    event_tx
+                        .send(TrayEvent::SwitchProfile(switch_to.clone()))
+                        .ok();
+                    ctx.request_repaint();
+                })
+                .unwrap();
+            profile_items.push((id, name));
+        }
+
+        tray.inner_mut().add_separator().unwrap();
+
+        let restore_tx = event_tx.clone();
+        let restore_ctx = ctx.clone();
+        tray.add_menu_item("Restore last session", move || {
+            restore_tx.send(TrayEvent::RestoreLastSession).ok();
+            restore_ctx.request_repaint();
+        })
+        .unwrap();
+
+        let undo_tx = event_tx.clone();
+        let undo_ctx = ctx.clone();
+        tray.add_menu_item("Undo last sweep", move || {
+            undo_tx.send(TrayEvent::Undo).ok();
+            undo_ctx.request_repaint();
+        })
+        .unwrap();
+
+        let hide_tx = tx.clone();
+        tray.add_menu_item("Hide", move || {
+            hide_tx.send(Message::Hide).unwrap();
+        })
+        .unwrap();
+
+        let unhide_tx = tx.clone();
+        tray.add_menu_item("Unhide", move || {
+            unhide_tx.send(Message::Unhide).unwrap();
+        })
+        .unwrap();
+
+        tray.inner_mut().add_separator().unwrap();
+
+        let quit_tx = tx.clone();
+        tray.add_menu_item("Quit", move || {
+            quit_tx.send(Message::Quit).unwrap();
+        })
+        .unwrap();
+
+        loop {
+            match rx.recv() {
+                Ok(Message::Quit) => {
+                    println!("Quit");
+                    break;
+                }
+                Ok(Message::Hide) => {
+                    println!("Hide");
+                    // We hackily do the hiding by just making the app 0 pixels in size
+                    // The visibility of the window never toggles back because of an eFrame bug (egui #5229)
+                    // so oh well
+                    let viewport = egui::ViewportId::ROOT;
+                    ctx.send_viewport_cmd_to(viewport, egui::ViewportCommand::Decorations(false));
+                    ctx.send_viewport_cmd_to(
+                        viewport,
+                        egui::ViewportCommand::InnerSize([0.0, 0.0].into()),
+                    );
+                    ctx.send_viewport_cmd_to(
+                        viewport,
+                        egui::ViewportCommand::OuterPosition(egui::pos2(-10000.0, -10000.0)),
+                    );
+                    ctx.request_repaint();
+                }
+                Ok(Message::Unhide) => {
+                    println!("Unhide");
+                    show_window(&ctx);
+                }
+                Ok(Message::Tooltip(tooltip)) => {
+                    tray.inner_mut().set_tooltip(&tooltip).ok();
+                }
+                Ok(Message::ActiveProfile(active)) => {
+                    for (id, name) in &profile_items {
+                        tray.inner_mut()
+                            .set_menu_item_label(&profile_label(name, *name == active), *id)
+                            .ok();
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    handle
+}
@@ -0,0 +1,185 @@
+//! Keeping apps closed: whatever the blocked list (and optionally the killlist) matches
+//! gets asked to close once its window has been up for the rule's grace period.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use jiff::civil::DateTime;
+
+use crate::dry_run::is_self;
+use crate::processes::{ProcessList, ToClose};
+use crate::rules::{Rule, first_match};
+use crate::schedule::Hours;
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Watch {
+    pub enabled: bool,
+    /// Blocked apps, with the seconds each one gets before it's closed.
+    pub blocked: BTreeMap<Rule, u64>,
+    /// Keeps the active profile's killlist closed as well.
+    pub killlist: bool,
+    /// Seconds killlisted apps get.
+    pub killlist_grace_secs: u64,
+    /// Only watching during these, always if there are none.
+    pub hours: Vec<Hours>,
+
+    // when we first saw a window of a blocked process, by pid
+    #[serde(skip)]
+    seen: BTreeMap<u32, Instant>,
+
+    // asked once already, the close strategy takes it from there
+    #[serde(skip)]
+    asked: BTreeSet<u32>,
+}
+
+// what's being watched for, not what's been seen so far
+impl PartialEq for Watch {
+    fn eq(&self, other: &Self) -> bool {
+        self.enabled == other.enabled
+            && self.blocked == other.blocked
+            && self.killlist == other.killlist
+            && self.killlist_grace_secs == other.killlist_grace_secs
+            && self.hours == other.hours
+    }
+}
+
+impl Eq for Watch {}
+
+impl Watch {
+    /// Watches for whatever `rules` says from now on, what's been seen so far stays.
+    pub fn follow(&mut self, rules: &Self) {
+        if self == rules {
+            return;
+        }
+        self.enabled = rules.enabled;
+        self.blocked.clone_from(&rules.blocked);
+        self.killlist = rules.killlist;
+        self.killlist_grace_secs = rules.killlist_grace_secs;
+        self.hours.clone_from(&rules.hours);
+    }
+
+    pub fn is_active(&self, now: DateTime) -> bool {
+        self.enabled
+            && (self.hours.is_empty() || self.hours.iter().any(|hours| hours.contains(now)))
+    }
+
+    /// A window of `pid` opened `at`, grace periods count from then instead of from the
+    /// first list it shows up in.
+    pub fn saw(&mut self, pid: u32, at: Instant) {
+        let seen = self.seen.entry(pid).or_insert(at);
+        *seen = (*seen).min(at);
+    }
+
+    /// Everything that's overstayed its grace by `now`. Call it with every fresh process
+    /// list, that's how it knows how long things have been around.
+    pub fn due(&mut self, killlist: &BTreeSet<Rule>, list: &ProcessList, now: Instant) -> ToClose {
+        let mut due = ToClose::new();
+        let mut present = BTreeSet::new();
+        for (name, processes) in list {
+            if is_self(name) {
+                continue;
+            }
+            for (pid, process) in processes {
+                if *pid == std::process::id() || !process.windows.iter().any(|w| w.visible) {
+                    continue;
+                }
+                let grace = first_match(self.blocked.keys(), process)
+                    .map(|rule| self.blocked[rule])
+                    .or_else(|| {
+                        first_match(killlist, process)
+                            .filter(|_| self.killlist)
+                            .map(|_| self.killlist_grace_secs)
+                    });
+                let Some(grace) = grace else {
+                    continue;
+                };
+                present.insert(*pid);
+                let seen = *self.seen.entry(*pid).or_insert(now);
+                if now.saturating_duration_since(seen) >= Duration::from_secs(grace)
+                    && self.asked.insert(*pid)
+                {
+                    log::info!("Watch closing {name} ({pid})");
+                    due.push((name.clone(), *pid, process.clone()));
+                }
+            }
+        }
+        // forgetting what's gone, a new process might get the same pid
+        self.seen.retain(|pid, _| present.contains(pid));
+        self.asked.retain(|pid| present.contains(pid));
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::time::{Duration, Instant};
+
+    use super::Watch;
+    use crate::backend::mock::{MockBackend, MockWindow};
+    use crate::processes::collect_processes;
+    use crate::rules::Rule;
+
+    #[test]
+    fn blocked_apps_get_their_grace_and_one_ask() {
+        let mock = MockBackend::new()
+            .process(100, "discord.exe")
+            .window(MockWindow::new(1, 100).title("Discord"))
+            .process(200, "steam.exe")
+            .window(MockWindow::new(2, 200).title("Steam"))
+            .process(300, "code.exe")
+            .window(MockWindow::new(3, 300));
+        let list = collect_processes(&mock, mock.processes(), true);
+        let mut watch = Watch {
+            enabled: true,
+            blocked: [(Rule::name("discord.exe"), 10)].into(),
+            killlist: true,
+            ..Watch::default()
+        };
+        let killlist = BTreeSet::from([Rule::name("steam.exe")]);
+        let names = |due: Vec<(String, u32, _)>| -> Vec<String> {
+            due.into_iter().map(|(name, _, _)| name).collect()
+        };
+
+        let start = Instant::now();
+        assert_eq!(
+            names(watch.due(&killlist, &list, start)),
+            ["steam.exe"],
+            "the killlist gets no grace by default"
+        );
+        assert_eq!(
+            names(watch.due(&killlist, &list, start + Duration::from_secs(5))),
+            Vec::<String>::new(),
+            "discord still has time, steam was asked already"
+        );
+        assert_eq!(
+            names(watch.due(&killlist, &list, start + Duration::from_secs(10))),
+            ["discord.exe"],
+            "time's up"
+        );
+    }
+
+    #[test]
+    fn grace_counts_from_when_the_window_opened() {
+        let mock = MockBackend::new()
+            .process(100, "discord.exe")
+            .window(MockWindow::new(1, 100));
+        let list = collect_processes(&mock, mock.processes(), true);
+        let mut watch = Watch {
+            enabled: true,
+            blocked: [(Rule::name("discord.exe"), 10)].into(),
+            ..Watch::default()
+        };
+
+        let opened = Instant::now();
+        watch.saw(100, opened);
+        assert_eq!(
+            watch
+                .due(&BTreeSet::new(), &list, opened + Duration::from_secs(10))
+                .len(),
+            1,
+            "the list came late, the window didn't"
+        );
+    }
+}
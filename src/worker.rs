@@ -0,0 +1,724 @@
+//! Keeps track of every process and window on a thread of its own.
+//!
+//! Backends that say when windows open and close keep it up to date one window at a time,
+//! otherwise it looks again every couple of seconds. Either way it only wakes the window
+//! when something changed, so painting never waits on sysinfo. Sweeping hotkeys, scheduled
+//! sweeps and watch mode are dealt with here as well, they work while the window is hidden
+//! and not painting at all.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+use crate::backend::{WindowBackend, WindowEvent, WindowId};
+use crate::classifier::Classifier;
+use crate::close::{Outcome, Target, start_sweep};
+use crate::dry_run::sweep_targets;
+use crate::hotkey::{Action, Hotkey, HotkeyEvent, RegisterError};
+use crate::processes::{
+    ProcessList, add_window, collect_processes, forget_window, process_infos, track_window,
+};
+use crate::profiles::Profiles;
+use crate::session::{SavedApp, Sessions};
+use crate::watch::Watch;
+
+/// How often it looks without being poked.
+pub const INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often it looks anyway when the backend says what changes, in case it missed some.
+pub const SAFETY_NET: Duration = Duration::from_secs(30);
+
+// how often grace periods get checked while watching, without looking again each time
+const WATCH_TICK: Duration = Duration::from_secs(1);
+
+/// Every process with a window, unfiltered, sysinfo's kept up to date in `sys`.
+pub fn enumerate(sys: &mut System, backend: &dyn WindowBackend) -> ProcessList {
+    // names and start times always come along, the rest doesn't change during a process
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_exe(UpdateKind::OnlyIfNotSet)
+            .with_cmd(UpdateKind::OnlyIfNotSet)
+            .with_cwd(UpdateKind::OnlyIfNotSet),
+    );
+    collect_processes(backend, &process_infos(sys), true)
+}
+
+/// What the worker needs to sweep by itself, the app keeps it in sync.
+#[derive(Clone, Debug, Default)]
+pub struct SweepRules {
+    pub profiles: Profiles,
+    pub classifier: Classifier,
+    /// There's a countdown in the window, the next sweeping hotkey calls it off.
+    pub counting_down: bool,
+    /// Whether to keep what it closes as the last session, and where.
+    pub snapshot_sweeps: bool,
+    pub sessions_path: Option<PathBuf>,
+    /// What watch mode keeps closed, the worker keeps track of how long things are open.
+    pub watch: Watch,
+}
+
+/// A sweep the worker did, for the window to report on whenever it gets to it.
+#[derive(Debug)]
+pub struct Swept {
+    pub profile: String,
+    pub targets: usize,
+    pub results: Receiver<(Target, Outcome)>,
+    pub at: Instant,
+    /// What it closed, already in the sessions file if there is one.
+    pub saved: Vec<SavedApp>,
+}
+
+/// What the worker has for the window.
+#[derive(Debug)]
+pub enum Update {
+    /// Something opened, closed or changed.
+    Processes(ProcessList),
+    Swept(Swept),
+    /// Hotkeys it can't deal with alone, countdowns and everything that isn't a sweep.
+    Hotkey(HotkeyEvent),
+    /// A scheduled sweep it can't do alone, with a countdown or next to one.
+    Scheduled(String),
+    /// The hotkeys the desktop wouldn't give us, once it got round to answering.
+    Bound(Vec<(Hotkey, RegisterError)>),
+    /// A taskbar window opened or closed, with its process. Focus changes and the like stay
+    /// here, the window has no use for them.
+    Window {
+        event: WindowEvent,
+        pid: u32,
+        name: String,
+    },
+}
+
+// what gets the worker going between looks
+enum Wakeup {
+    Look,
+    Window(WindowEvent),
+    // only for the watch, it's not time to look yet
+    Tick,
+    // the scheduler says it's time for a sweep with this profile
+    Scheduled(String),
+    // the desktop answered a rebind, only the window cares
+    Bound(Vec<(Hotkey, RegisterError)>),
+}
+
+fn owner(list: &ProcessList, id: WindowId) -> Option<u32> {
+    list.values()
+        .flatten()
+        .find(|(_, process)| process.windows.iter().any(|window| window.id == id))
+        .map(|(pid, _)| *pid)
+}
+
+// the process of a window that's in the taskbar, with its name
+fn shown_by(list: &ProcessList, id: WindowId) -> Option<(u32, String)> {
+    list.iter().find_map(|(name, processes)| {
+        processes
+            .iter()
+            .find(|(_, process)| {
+                process
+                    .windows
+                    .iter()
+                    .any(|window| window.id == id && window.in_taskbar)
+            })
+            .map(|(pid, _)| (*pid, name.clone()))
+    })
+}
+
+// keeps `list` up to date with one event, only asking sysinfo when a new process shows up.
+// whether the list changed, and whose window it was
+fn apply(
+    backend: &dyn WindowBackend,
+    list: &mut ProcessList,
+    event: WindowEvent,
+    lookup: &mut dyn FnMut() -> ProcessList,
+) -> (bool, Option<u32>) {
+    match event {
+        // hidden ones stay, as they'd be after a look
+        WindowEvent::Opened(id) | WindowEvent::Hidden(id) => {
+            let Some((pid, window)) = track_window(backend, id) else {
+                // gone already, or nobody owns it
+                return (false, None);
+            };
+            if add_window(list, pid, window) {
+                return (true, Some(pid));
+            }
+            let fresh = lookup();
+            let changed = fresh != *list;
+            *list = fresh;
+            (changed, Some(pid))
+        }
+        WindowEvent::Closed(id) => {
+            let pid = owner(list, id);
+            (forget_window(list, id), pid)
+        }
+        WindowEvent::Focused(id) => (false, id.and_then(|id| backend.owner_pid(id))),
+    }
+}
+
+// what a hotkey comes down to
+#[derive(Debug, PartialEq, Eq)]
+enum Handled {
+    Sweep { profile: String, spare: Option<u32> },
+    Forward(HotkeyEvent),
+}
+
+// countdowns need the window, and so does calling one off
+fn right_away(rules: &SweepRules, profile: &str) -> bool {
+    !rules.counting_down
+        && rules
+            .profiles
+            .get(profile)
+            .is_some_and(|found| found.countdown_secs == 0)
+}
+
+fn handle(
+    event: HotkeyEvent,
+    rules: &SweepRules,
+    focused: impl FnOnce() -> Option<u32>,
+) -> Handled {
+    let (profile, except_focused) = match &event {
+        HotkeyEvent::Kill { profile } => (profile.clone(), false),
+        HotkeyEvent::Action(Action::Sweep) => (rules.profiles.active_name().to_owned(), false),
+        HotkeyEvent::Action(Action::SweepExceptFocused) => {
+            (rules.profiles.active_name().to_owned(), true)
+        }
+        HotkeyEvent::Action(_) => return Handled::Forward(event),
+    };
+    if !right_away(rules, &profile) {
+        return Handled::Forward(event);
+    }
+    Handled::Sweep {
+        profile,
+        spare: if except_focused { focused() } else { None },
+    }
+}
+
+fn sweep(
+    backend: &Arc<dyn WindowBackend>,
+    list: &ProcessList,
+    rules: &SweepRules,
+    profile: String,
+    spare: Option<u32>,
+) -> Option<Swept> {
+    let found = rules.profiles.get(&profile)?;
+    let to_close = sweep_targets(list, found, &rules.classifier, false, spare);
+    let (targets, results) = start_sweep(backend, found, &to_close);
+    log::info!("Swept with {profile}, {} to keep an eye on", targets.len());
+
+    let mut saved = Vec::new();
+    if rules.snapshot_sweeps && !targets.is_empty() {
+        saved = targets
+            .iter()
+            .filter_map(|target| target.saved.clone())
+            .collect();
+        if let Some(path) = &rules.sessions_path {
+            Sessions::default().remember_sweep(Some(path), saved.clone());
+        }
+    }
+    Some(Swept {
+        profile,
+        targets: targets.len(),
+        results,
+        at: Instant::now(),
+        saved,
+    })
+}
+
+// closes what the watch caught, apart from sweeps: no report, undo or snapshot.
+// whether it's watching at all
+fn keep_watch(
+    backend: &Arc<dyn WindowBackend>,
+    list: &ProcessList,
+    rules: &SweepRules,
+    watch: &mut Watch,
+    at: jiff::civil::DateTime,
+    now: Instant,
+) -> bool {
+    watch.follow(&rules.watch);
+    if !watch.is_active(at) {
+        return watch.enabled;
+    }
+    let profile = rules.profiles.active();
+    let due = watch.due(&profile.killlist, list, now);
+    if !due.is_empty() {
+        // nobody's waiting on how it went
+        start_sweep(backend, profile, &due);
+    }
+    true
+}
+
+/// Handle to the worker thread, does nothing if there isn't one.
+#[derive(Default)]
+pub struct Worker {
+    pokes: Option<Sender<Wakeup>>,
+    updates: Option<Receiver<Update>>,
+    rules: Arc<Mutex<SweepRules>>,
+}
+
+impl Worker {
+    /// Sweeps on `hotkeys` where it can by itself, `wake` gets the window to pick up
+    /// whatever it has.
+    pub fn spawn(
+        backend: Arc<dyn WindowBackend>,
+        hotkeys: Receiver<HotkeyEvent>,
+        rules: SweepRules,
+        wake: impl Fn() + Send + 'static,
+    ) -> Self {
+        let mut sys = System::new();
+        let looking = backend.clone();
+        let lookup = move || enumerate(&mut sys, looking.as_ref());
+        Self::with_lookup(backend, lookup, hotkeys, rules, wake)
+    }
+
+    /// The same, with something other than sysinfo saying what's open.
+    pub fn with_lookup(
+        backend: Arc<dyn WindowBackend>,
+        lookup: impl FnMut() -> ProcessList + Send + 'static,
+        hotkeys: Receiver<HotkeyEvent>,
+        rules: SweepRules,
+        wake: impl Fn() + Send + 'static,
+    ) -> Self {
+        let (pokes, poked) = mpsc::channel();
+        let (updates_tx, updates) = mpsc::channel();
+        let rules = Arc::new(Mutex::new(rules));
+        let shared = rules.clone();
+        let events = pokes.clone();
+        let subscribed = backend.subscribe(Box::new(move |event| {
+            events.send(Wakeup::Window(event)).is_ok()
+        }));
+        let interval = if subscribed { SAFETY_NET } else { INTERVAL };
+        // false once the app's gone
+        let send = move |update| {
+            let sent = updates_tx.send(update).is_ok();
+            if sent {
+                wake();
+            }
+            sent
+        };
+        thread::spawn(move || run(&backend, lookup, interval, &poked, &hotkeys, &shared, &send));
+        Self {
+            pokes: Some(pokes),
+            updates: Some(updates),
+            rules,
+        }
+    }
+
+    /// Where the scheduler's sweeps go, false once the worker's gone.
+    pub fn scheduled(&self) -> impl Fn(String) -> bool + Send + 'static {
+        let pokes = self.pokes.clone();
+        move |profile| {
+            pokes
+                .as_ref()
+                .is_some_and(|pokes| pokes.send(Wakeup::Scheduled(profile)).is_ok())
+        }
+    }
+
+    /// Where hotkey listeners send what they couldn't bind, when they only know later.
+    pub fn answered(&self) -> impl Fn(Vec<(Hotkey, RegisterError)>) + Send + Sync + 'static {
+        let pokes = self.pokes.clone();
+        move |failures| {
+            if let Some(pokes) = &pokes {
+                pokes.send(Wakeup::Bound(failures)).ok();
+            }
+        }
+    }
+
+    /// Gets it to look again right away, hotkeys get its attention with this too.
+    pub fn poker(&self) -> impl Fn() + Send + Sync + 'static {
+        let pokes = self.pokes.clone();
+        move || {
+            if let Some(pokes) = &pokes {
+                pokes.send(Wakeup::Look).ok();
+            }
+        }
+    }
+
+    pub fn sync_rules(&self, change: impl FnOnce(&mut SweepRules)) {
+        change(&mut self.rules.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    pub fn try_recv(&self) -> Option<Update> {
+        self.updates.as_ref()?.try_recv().ok()
+    }
+}
+
+fn run(
+    backend: &Arc<dyn WindowBackend>,
+    mut lookup: impl FnMut() -> ProcessList,
+    interval: Duration,
+    poked: &Receiver<Wakeup>,
+    hotkeys: &Receiver<HotkeyEvent>,
+    rules: &Mutex<SweepRules>,
+    send: &dyn Fn(Update) -> bool,
+) {
+    let mut list = ProcessList::new();
+    let mut watch = Watch::default();
+    let mut first = true;
+    let mut wakeup = Wakeup::Look;
+    let mut looked = Instant::now();
+    loop {
+        let changed = match &wakeup {
+            // sweeps go after a look, so they close what's open now
+            Wakeup::Look | Wakeup::Scheduled(_) => {
+                looked = Instant::now();
+                let fresh = lookup();
+                let changed = fresh != list;
+                list = fresh;
+                changed
+            }
+            Wakeup::Window(event) => {
+                let event = *event;
+                // closed ones are only in the list before
+                let closed = match event {
+                    WindowEvent::Closed(id) => shown_by(&list, id),
+                    _ => None,
+                };
+                let (changed, pid) = apply(backend.as_ref(), &mut list, event, &mut lookup);
+                // grace periods count from when the window opened, not the next look
+                if let (WindowEvent::Opened(_), Some(pid)) = (event, pid) {
+                    watch.saw(pid, Instant::now());
+                }
+                // every focus change would be a repaint for nothing
+                let shown = match event {
+                    WindowEvent::Opened(id) => shown_by(&list, id),
+                    _ => closed,
+                };
+                if let Some((pid, name)) = shown {
+                    if !send(Update::Window { event, pid, name }) {
+                        return;
+                    }
+                }
+                changed
+            }
+            Wakeup::Bound(failures) => {
+                if !send(Update::Bound(failures.clone())) {
+                    return;
+                }
+                false
+            }
+            Wakeup::Tick => false,
+        };
+        if (std::mem::take(&mut first) || changed) && !send(Update::Processes(list.clone())) {
+            return;
+        }
+
+        // after looking, so hotkeys sweep what's open now
+        while let Ok(event) = hotkeys.try_recv() {
+            let rules = rules.lock().unwrap_or_else(PoisonError::into_inner).clone();
+            let focused = || {
+                let window = backend.focused_window()?;
+                backend.owner_pid(window)
+            };
+            let update = match handle(event.clone(), &rules, focused) {
+                Handled::Sweep { profile, spare } => sweep(backend, &list, &rules, profile, spare)
+                    .map_or(Update::Hotkey(event), Update::Swept),
+                Handled::Forward(event) => Update::Hotkey(event),
+            };
+            if !send(update) {
+                return;
+            }
+        }
+
+        if let Wakeup::Scheduled(profile) = wakeup {
+            let rules = rules.lock().unwrap_or_else(PoisonError::into_inner).clone();
+            let swept = right_away(&rules, &profile)
+                .then(|| sweep(backend, &list, &rules, profile.clone(), None))
+                .flatten();
+            if !send(swept.map_or(Update::Scheduled(profile), Update::Swept)) {
+                return;
+            }
+        }
+
+        let watching = {
+            let rules = rules.lock().unwrap_or_else(PoisonError::into_inner);
+            let at = jiff::Zoned::now().datetime();
+            keep_watch(backend, &list, &rules, &mut watch, at, Instant::now())
+        };
+
+        // grace periods run out while nothing happens too
+        let timeout = if watching { WATCH_TICK } else { interval };
+        wakeup = match poked.recv_timeout(timeout) {
+            Ok(wakeup) => wakeup,
+            Err(RecvTimeoutError::Timeout) if looked.elapsed() < interval => Wakeup::Tick,
+            Err(RecvTimeoutError::Timeout) => Wakeup::Look,
+            // the app's gone
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Arc;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    use super::{Handled, SweepRules, Update, Worker, apply, handle, keep_watch, shown_by};
+    use crate::backend::WindowEvent;
+    use crate::backend::mock::{MockBackend, MockWindow};
+    use crate::hotkey::{Action, Hotkey, HotkeyEvent, RegisterError};
+    use crate::processes::collect_processes;
+    use crate::profiles::DEFAULT_PROFILE;
+    use crate::rules::Rule;
+    use crate::watch::Watch;
+
+    fn kill(profile: &str) -> HotkeyEvent {
+        HotkeyEvent::Kill {
+            profile: profile.to_owned(),
+        }
+    }
+
+    #[test]
+    fn countdowns_are_left_to_the_window() {
+        let mut rules = SweepRules::default();
+        assert_eq!(
+            handle(kill(DEFAULT_PROFILE), &rules, || None),
+            Handled::Sweep {
+                profile: DEFAULT_PROFILE.to_owned(),
+                spare: None
+            },
+            "no countdown, no need for the window"
+        );
+        assert_eq!(
+            handle(
+                HotkeyEvent::Action(Action::SweepExceptFocused),
+                &rules,
+                || Some(7)
+            ),
+            Handled::Sweep {
+                profile: DEFAULT_PROFILE.to_owned(),
+                spare: Some(7)
+            },
+            "leaving the focused app alone"
+        );
+
+        rules.counting_down = true;
+        assert_eq!(
+            handle(kill(DEFAULT_PROFILE), &rules, || None),
+            Handled::Forward(kill(DEFAULT_PROFILE)),
+            "that one calls off the countdown"
+        );
+        assert_eq!(
+            handle(kill("nope"), &SweepRules::default(), || None),
+            Handled::Forward(kill("nope")),
+            "the window says there's no such profile"
+        );
+        assert_eq!(
+            handle(
+                HotkeyEvent::Action(Action::ShowWindow),
+                &SweepRules::default(),
+                || None
+            ),
+            Handled::Forward(HotkeyEvent::Action(Action::ShowWindow)),
+            "not a sweep"
+        );
+    }
+
+    #[test]
+    fn hotkeys_sweep_without_the_window() {
+        let mock = Arc::new(
+            MockBackend::new()
+                .process(100, "code.exe")
+                .window(MockWindow::new(1, 100).title("main.rs - Visual Studio Code"))
+                .process(200, "notepad.exe")
+                .window(MockWindow::new(2, 200).title("Untitled - Notepad")),
+        );
+        let looking = mock.clone();
+        let lookup = move || collect_processes(looking.as_ref(), looking.processes(), true);
+        let (hotkeys, hotkey_rx) = mpsc::channel();
+        let worker = Worker::with_lookup(
+            mock.clone(),
+            lookup,
+            hotkey_rx,
+            SweepRules::default(),
+            || {},
+        );
+
+        hotkeys
+            .send(kill(DEFAULT_PROFILE))
+            .expect("the worker is listening");
+        worker.poker()();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut swept = None;
+        while swept.is_none() && Instant::now() < deadline {
+            match worker.try_recv() {
+                Some(Update::Swept(sweep)) => swept = Some(sweep),
+                Some(_) => {}
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        let swept = swept.expect("swept in time");
+        assert_eq!(swept.profile, DEFAULT_PROFILE, "with the hotkey's profile");
+        assert_eq!(mock.closed(), [1, 2], "nobody painted anything");
+    }
+
+    #[test]
+    fn scheduled_sweeps_go_without_the_window() {
+        let mock = Arc::new(
+            MockBackend::new()
+                .process(200, "notepad.exe")
+                .window(MockWindow::new(2, 200).title("Untitled - Notepad")),
+        );
+        let looking = mock.clone();
+        let lookup = move || collect_processes(looking.as_ref(), looking.processes(), true);
+        let (_hotkeys, hotkey_rx) = mpsc::channel();
+        let worker = Worker::with_lookup(
+            mock.clone(),
+            lookup,
+            hotkey_rx,
+            SweepRules::default(),
+            || {},
+        );
+        let next = |wanted: fn(&Update) -> bool| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                match worker.try_recv() {
+                    Some(update) if wanted(&update) => return Some(update),
+                    Some(_) => {}
+                    None => std::thread::sleep(Duration::from_millis(10)),
+                }
+            }
+            None
+        };
+
+        assert!(
+            worker.scheduled()(DEFAULT_PROFILE.to_owned()),
+            "the worker is listening"
+        );
+        assert!(
+            next(|update| matches!(update, Update::Swept(_))).is_some(),
+            "swept in time"
+        );
+        assert_eq!(mock.closed(), [2], "nobody painted anything");
+
+        worker.sync_rules(|rules| rules.counting_down = true);
+        worker.scheduled()(DEFAULT_PROFILE.to_owned());
+        assert!(
+            next(|update| matches!(update, Update::Scheduled(_))).is_some(),
+            "the window decides what happens next to a countdown"
+        );
+        assert_eq!(mock.closed(), [2], "nothing more closed");
+
+        let taken = vec![(Hotkey::DEFAULT, RegisterError::Taken)];
+        worker.answered()(taken.clone());
+        assert!(
+            matches!(
+                next(|update| matches!(update, Update::Bound(_))),
+                Some(Update::Bound(failures)) if failures == taken
+            ),
+            "late answers about hotkeys get to the window the same way"
+        );
+    }
+
+    #[test]
+    fn windows_are_kept_track_of_one_by_one() {
+        let before = MockBackend::new()
+            .process(100, "code.exe")
+            .window(MockWindow::new(1, 100))
+            .process(200, "notepad.exe")
+            .window(MockWindow::new(3, 200));
+        let now = MockBackend::new()
+            .process(100, "code.exe")
+            .window(MockWindow::new(1, 100))
+            .window(MockWindow::new(2, 100).title("second window"))
+            .process(300, "gedit")
+            .window(MockWindow::new(4, 300));
+        let mut list = collect_processes(&before, before.processes(), true);
+        let lookups = Cell::new(0);
+        let mut lookup = || {
+            lookups.set(lookups.get() + 1);
+            collect_processes(&now, now.processes(), true)
+        };
+
+        assert_eq!(
+            apply(&now, &mut list, WindowEvent::Opened(2), &mut lookup),
+            (true, Some(100)),
+            "a second window of something we know"
+        );
+        assert_eq!(
+            list["code.exe"][&100].windows.len(),
+            2,
+            "added to its process"
+        );
+        assert_eq!(
+            shown_by(&list, 2),
+            Some((100, "code.exe".to_owned())),
+            "worth telling the window about"
+        );
+        assert_eq!(
+            apply(&now, &mut list, WindowEvent::Closed(3), &mut lookup),
+            (true, Some(200)),
+            "notepad closed"
+        );
+        assert!(
+            !list.contains_key("notepad.exe"),
+            "and its process goes with its last window"
+        );
+        assert_eq!(lookups.get(), 0, "sysinfo wasn't needed so far");
+
+        assert_eq!(
+            apply(&now, &mut list, WindowEvent::Opened(4), &mut lookup),
+            (true, Some(300)),
+            "a new process"
+        );
+        assert_eq!(lookups.get(), 1, "needs a proper look");
+        assert_eq!(
+            list,
+            collect_processes(&now, now.processes(), true),
+            "ends up the same as looking every time"
+        );
+
+        let hidden = MockBackend::new()
+            .process(100, "code.exe")
+            .window(MockWindow::new(1, 100).hidden());
+        assert_eq!(
+            apply(&hidden, &mut list, WindowEvent::Hidden(1), &mut lookup),
+            (true, Some(100)),
+            "a window went away without closing"
+        );
+        assert!(
+            !list["code.exe"][&100].windows[0].visible,
+            "it's not listed as shown anymore"
+        );
+        assert_eq!(shown_by(&list, 1), None, "nor told about");
+    }
+
+    #[test]
+    fn the_watch_closes_quietly() {
+        let mock = MockBackend::new()
+            .process(100, "code.exe")
+            .window(MockWindow::new(1, 100))
+            .process(200, "notepad.exe")
+            .window(MockWindow::new(2, 200));
+        let list = collect_processes(&mock, mock.processes(), true);
+        let mock = Arc::new(mock);
+        let backend: Arc<dyn crate::backend::WindowBackend> = mock.clone();
+        let mut rules = SweepRules::default();
+        rules.watch.enabled = true;
+        rules.watch.blocked.insert(Rule::name("notepad.exe"), 0);
+        let mut watch = Watch::default();
+        // 2026-10-19 is a monday
+        let monday = jiff::civil::date(2026, 10, 19).at(12, 0, 0, 0);
+
+        assert!(
+            keep_watch(&backend, &list, &rules, &mut watch, monday, Instant::now()),
+            "watching"
+        );
+        assert_eq!(mock.closed(), [2], "notepad is blocked");
+
+        rules
+            .watch
+            .hours
+            .push("Sat,Sun 00:00-23:59".parse().expect("valid hours"));
+        rules.watch.blocked.insert(Rule::name("code.exe"), 0);
+        keep_watch(&backend, &list, &rules, &mut watch, monday, Instant::now());
+        assert_eq!(mock.closed(), [2], "off on weekdays");
+    }
+}